@@ -1,17 +1,18 @@
-X = 500  -- en
-Y = 700  -- boy/yükseklik
-modelParameters = ""
-materialThickness = 18
+-- Panel defaults, the host application may set these globals before this file runs
+X = X or 500  -- en
+Y = Y or 700  -- boy/yükseklik
+modelParameters = modelParameters or ""
+materialThickness = materialThickness or 18
 offset = 20
-edge1layer="LMM0"
-edge2layer="LMM1"
-edge3layer="LMM2"
-edge4layer="LMM3"
-edge1thickness=0.1
-edge2thickness=0.2
-edge3thickness=0.3
-edge4thickness=0.4
-doesSizeIncludeEdgeThickness = "false"
+edge1layer = edge1layer or "LMM0"
+edge2layer = edge2layer or "LMM1"
+edge3layer = edge3layer or "LMM2"
+edge4layer = edge4layer or "LMM3"
+edge1thickness = edge1thickness or 0.1
+edge2thickness = edge2thickness or 0.2
+edge3thickness = edge3thickness or 0.3
+edge4thickness = edge4thickness or 0.4
+doesSizeIncludeEdgeThickness = doesSizeIncludeEdgeThickness or "false"
 ADekoLib = require("ADekoLib")
 require("turtle")
 pnsz(2)
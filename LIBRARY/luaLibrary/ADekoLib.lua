@@ -974,6 +974,10 @@ end
 
 ------------------------------------------------
 -- Returns the resulting polyline, which is offset by value, out of inputPoints.
+-- Takes straight segments only, bulges are not supported.
+-- ADekoNative.offset(inputPoints, value) is a native offset with the same sign, not a drop-in
+-- replacement: it also takes bulges, cuts away the parts that come too close to inputPoints and
+-- returns a list of polylines, which is empty when the offset collapses.
 function ADekoLib.offSet(inputPoints, value)
 
   local points = {}
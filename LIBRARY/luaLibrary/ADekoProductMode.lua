@@ -1,20 +1,21 @@
-X = 500
-Y = 700
-Z = 500
-modelParameters = ""
-bodyMaterialThickness = 18
-doorMaterialThickness = 18
-bodyMaterial = "Mat Beyaz MDF"
-doorMaterial = "Kestane Orma"
-edge1layer="LMM0"
-edge2layer="LMM1"
-edge3layer="LMM2"
-edge4layer="LMM3"
-edge1thickness=0
-edge2thickness=0
-edge3thickness=0
-edge4thickness=0
-doesSizeIncludeEdgeThickness = "false"
+-- Product defaults, the host application may set these globals before this file runs
+X = X or 500
+Y = Y or 700
+Z = Z or 500
+modelParameters = modelParameters or ""
+bodyMaterialThickness = bodyMaterialThickness or 18
+doorMaterialThickness = doorMaterialThickness or 18
+bodyMaterial = bodyMaterial or "Mat Beyaz MDF"
+doorMaterial = doorMaterial or "Kestane Orma"
+edge1layer = edge1layer or "LMM0"
+edge2layer = edge2layer or "LMM1"
+edge3layer = edge3layer or "LMM2"
+edge4layer = edge4layer or "LMM3"
+edge1thickness = edge1thickness or 0
+edge2thickness = edge2thickness or 0
+edge3thickness = edge3thickness or 0
+edge4thickness = edge4thickness or 0
+doesSizeIncludeEdgeThickness = doesSizeIncludeEdgeThickness or "false"
 ADekoLib = require("ADekoLib")
 require("turtle")
 offset = 20
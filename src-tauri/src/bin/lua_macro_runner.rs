@@ -0,0 +1,188 @@
+// Headless runner: executes a model file with the same engine as the editor, without the Tauri window.
+//
+// Usage: lua-macro-runner <model.lua> [options]
+//   --library <dir>            luaLibrary directory (default ./LIBRARY/luaLibrary)
+//   --mode <plain|debug|product>
+//   --params <file.json>       ModelParameters as JSON, the flags below override it
+//   --x <mm> --y <mm> --z <mm> --thickness <mm> --edge-thickness <mm>
+//   --model-parameters <text>  e.g. "edgeMargin=50, dir=-x"
+//   --mdir <0|90|180|270>
+//   --timeout-ms <ms> --instruction-limit <n>
+//   --output <file.json>       also writes the result to a file
+//
+// Prints the execution result, with the part model, as one JSON document on stdout.
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+use lua_macro_editor::lua_engine::{ExecutionLimits, LuaExecutionResult, ModelParameters, NativeLuaEngine};
+use lua_macro_editor::model_script::{build_model_script, ScriptMode};
+
+const DEFAULT_LIBRARY_PATH: &str = "./LIBRARY/luaLibrary";
+const DEFAULT_EXECUTION_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_INSTRUCTION_LIMIT: u64 = 2_000_000_000;
+
+// Exit codes: the script failed to run, or the command line itself was wrong
+const EXIT_SCRIPT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+struct RunnerOptions {
+    script_path: String,
+    library_path: String,
+    mode: ScriptMode,
+    parameters: ModelParameters,
+    limits: ExecutionLimits,
+    output_path: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Usage: lua-macro-runner <model.lua> [--library <dir>] [--mode plain|debug|product] [--params <file.json>]");
+            eprintln!("       [--x <mm>] [--y <mm>] [--z <mm>] [--thickness <mm>] [--edge-thickness <mm>]");
+            eprintln!("       [--model-parameters <text>] [--mdir <deg>] [--timeout-ms <ms>] [--instruction-limit <n>] [--output <file.json>]");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    match run(&options) {
+        Ok(result) if result.success => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(EXIT_SCRIPT_FAILED),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(EXIT_SCRIPT_FAILED)
+        }
+    }
+}
+
+fn run(options: &RunnerOptions) -> Result<LuaExecutionResult, String> {
+    let script_content = fs::read_to_string(&options.script_path)
+        .map_err(|e| format!("Failed to read '{}': {}", options.script_path, e))?;
+    let script_name = Path::new(&options.script_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("user_script.lua");
+
+    let (full_script, source_map) = build_model_script(&script_content, script_name, &options.library_path, options.mode);
+
+    let engine = NativeLuaEngine::new_with_library_path(Some(options.library_path.clone()))
+        .map_err(|e| format!("Failed to create Lua engine: {}", e))?;
+    engine.set_source_map(source_map);
+    engine.set_model_parameters(&options.parameters)
+        .map_err(|e| format!("Failed to set model parameters: {}", e))?;
+
+    let result = engine.execute_script_with_limits(&full_script, &options.limits);
+
+    // The result, part model included, is the only thing written to stdout; engine logging goes to stderr
+    let result_json = serde_json::to_string_pretty(&result)
+        .map_err(|e| format!("Failed to serialize the result: {}", e))?;
+    println!("{}", result_json);
+
+    if let Some(ref output_path) = options.output_path {
+        fs::write(output_path, &result_json)
+            .map_err(|e| format!("Failed to write '{}': {}", output_path, e))?;
+    }
+
+    if !result.success {
+        eprintln!("{}", result.error);
+    }
+
+    Ok(result)
+}
+
+fn parse_args(args: Vec<String>) -> Result<RunnerOptions, String> {
+    let mut script_path = None;
+    let mut library_path = DEFAULT_LIBRARY_PATH.to_string();
+    let mut mode = ScriptMode::Debug;
+    let mut params_path = None;
+    let mut overrides: Vec<(String, String)> = Vec::new();
+    let mut timeout_ms = DEFAULT_EXECUTION_TIMEOUT_MS;
+    let mut instruction_limit = DEFAULT_INSTRUCTION_LIMIT;
+    let mut output_path = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if script_path.replace(arg).is_some() {
+                return Err("Only one model file can be given".to_string());
+            }
+            continue;
+        }
+
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--library" => library_path = value,
+            "--mode" => {
+                mode = match value.as_str() {
+                    "plain" => ScriptMode::Plain,
+                    "debug" => ScriptMode::Debug,
+                    "product" => ScriptMode::Product,
+                    _ => return Err(format!("Unknown mode '{}'", value)),
+                }
+            }
+            "--params" => params_path = Some(value),
+            "--timeout-ms" => timeout_ms = parse_number(&arg, &value)?,
+            "--instruction-limit" => instruction_limit = parse_number(&arg, &value)?,
+            "--output" => output_path = Some(value),
+            "--x" | "--y" | "--z" | "--thickness" | "--edge-thickness" | "--model-parameters" | "--mdir" => {
+                overrides.push((arg, value))
+            }
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+
+    let script_path = script_path.ok_or("No model file given")?;
+
+    let mut parameters = match params_path {
+        Some(path) => {
+            let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+            serde_json::from_str(&content).map_err(|e| format!("Invalid model parameters in '{}': {}", path, e))?
+        }
+        None if mode == ScriptMode::Product => ModelParameters::product_defaults(),
+        None => ModelParameters::default(),
+    };
+
+    for (option, value) in overrides {
+        match option.as_str() {
+            "--x" => parameters.x = parse_number(&option, &value)?,
+            "--y" => parameters.y = parse_number(&option, &value)?,
+            "--z" => parameters.z = parse_number(&option, &value)?,
+            "--thickness" => {
+                let thickness = parse_number(&option, &value)?;
+                parameters.material_thickness = thickness;
+                parameters.body_material_thickness = thickness;
+                parameters.door_material_thickness = thickness;
+            }
+            "--edge-thickness" => {
+                let thickness = parse_number(&option, &value)?;
+                for edge_band in parameters.edge_bands.iter_mut() {
+                    edge_band.thickness = thickness;
+                }
+            }
+            "--model-parameters" => parameters.model_parameters = value,
+            "--mdir" => parameters.mdir = Some(parse_number(&option, &value)?),
+            _ => unreachable!(),
+        }
+    }
+    parameters.validate()?;
+
+    Ok(RunnerOptions {
+        script_path,
+        library_path,
+        mode,
+        parameters,
+        limits: ExecutionLimits {
+            timeout: Some(Duration::from_millis(timeout_ms)),
+            instruction_limit: Some(instruction_limit),
+            cancel_flag: None,
+        },
+        output_path,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value '{}' for {}", value, option))
+}
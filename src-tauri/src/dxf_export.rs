@@ -0,0 +1,259 @@
+use std::fmt::Display;
+use crate::part_model::{ModelShape, PanelFace, PartModel};
+
+// Application name of the extended data attached to every polyline
+const DXF_APP_NAME: &str = "ADEKO";
+
+/// Builds an ASCII DXF (AutoCAD 2000) drawing of the shapes on one face, each in the face's own X/Y
+/// coordinates, with one LWPOLYLINE per shape on its ADekoLib layer. The shape thickness is written as
+/// the polyline elevation and, together with the face and node Z values, as ADEKO extended data.
+pub fn build_dxf(shapes: &[ModelShape], face: PanelFace) -> String {
+    // Every face has its own X/Y plane, shapes of several faces in one model space would overlap
+    let shapes: Vec<&ModelShape> = shapes.iter().filter(|shape| shape.face == Some(face)).collect();
+
+    let mut layers: Vec<&str> = vec!["0"];
+    for layer in shapes.iter().map(|s| s.layer_name.as_str()) {
+        if !layer.is_empty() && !layers.contains(&layer) {
+            layers.push(layer);
+        }
+    }
+
+    // Everything after the header is written first, $HANDSEED has to be above every handle in it
+    let mut dxf = DxfWriter::default();
+    dxf.section("CLASSES");
+    dxf.end_section();
+
+    dxf.section("TABLES");
+    dxf.table("VPORT", 0);
+    dxf.end_table();
+    let ltype_table = dxf.table("LTYPE", 3);
+    for (name, description) in [("ByBlock", ""), ("ByLayer", ""), ("CONTINUOUS", "Solid line")] {
+        dxf.table_entry("LTYPE", &ltype_table, "AcDbLinetypeTableRecord");
+        dxf.pair(2, name);
+        dxf.pair(70, 0);
+        dxf.pair(3, description);
+        dxf.pair(72, 65);
+        dxf.pair(73, 0);
+        dxf.pair(40, 0.0);
+    }
+    dxf.end_table();
+    let layer_table = dxf.table("LAYER", layers.len());
+    for layer in &layers {
+        dxf.table_entry("LAYER", &layer_table, "AcDbLayerTableRecord");
+        dxf.pair(2, layer);
+        dxf.pair(70, 0);
+        dxf.pair(62, 7);
+        dxf.pair(6, "CONTINUOUS");
+    }
+    dxf.end_table();
+    let style_table = dxf.table("STYLE", 1);
+    dxf.table_entry("STYLE", &style_table, "AcDbTextStyleTableRecord");
+    dxf.pair(2, "STANDARD");
+    dxf.pair(70, 0);
+    dxf.pair(40, 0.0);
+    dxf.pair(41, 1.0);
+    dxf.pair(50, 0.0);
+    dxf.pair(71, 0);
+    dxf.pair(42, 2.5);
+    dxf.pair(3, "txt");
+    dxf.pair(4, "");
+    dxf.end_table();
+    for name in ["VIEW", "UCS"] {
+        dxf.table(name, 0);
+        dxf.end_table();
+    }
+    let appid_table = dxf.table("APPID", 2);
+    for name in ["ACAD", DXF_APP_NAME] {
+        dxf.table_entry("APPID", &appid_table, "AcDbRegAppTableRecord");
+        dxf.pair(2, name);
+        dxf.pair(70, 0);
+    }
+    dxf.end_table();
+    // DIMSTYLE is the one table whose records use 105 for their handle
+    let dimstyle_table = dxf.table("DIMSTYLE", 1);
+    dxf.pair(100, "AcDbDimStyleTable");
+    dxf.pair(0, "DIMSTYLE");
+    dxf.handle(105);
+    dxf.pair(330, &dimstyle_table);
+    dxf.pair(100, "AcDbSymbolTableRecord");
+    dxf.pair(100, "AcDbDimStyleTableRecord");
+    dxf.pair(2, "STANDARD");
+    dxf.pair(70, 0);
+    dxf.end_table();
+    let block_record_table = dxf.table("BLOCK_RECORD", 2);
+    let model_space = dxf.table_entry("BLOCK_RECORD", &block_record_table, "AcDbBlockTableRecord");
+    dxf.pair(2, "*Model_Space");
+    let paper_space = dxf.table_entry("BLOCK_RECORD", &block_record_table, "AcDbBlockTableRecord");
+    dxf.pair(2, "*Paper_Space");
+    dxf.end_table();
+    dxf.end_section();
+
+    dxf.section("BLOCKS");
+    for (name, block_record, in_paper_space) in [("*Model_Space", &model_space, false), ("*Paper_Space", &paper_space, true)] {
+        dxf.entity("BLOCK", block_record, "0", in_paper_space);
+        dxf.pair(100, "AcDbBlockBegin");
+        dxf.pair(2, name);
+        dxf.pair(70, 0);
+        dxf.pair(10, 0.0);
+        dxf.pair(20, 0.0);
+        dxf.pair(30, 0.0);
+        dxf.pair(3, name);
+        dxf.pair(1, "");
+        dxf.entity("ENDBLK", block_record, "0", in_paper_space);
+        dxf.pair(100, "AcDbBlockEnd");
+    }
+    dxf.end_section();
+
+    dxf.section("ENTITIES");
+    for shape in shapes {
+        write_polyline(&mut dxf, shape, &model_space);
+    }
+    dxf.end_section();
+
+    // Named object dictionary, AutoCAD expects at least the ACAD_GROUP entry
+    dxf.section("OBJECTS");
+    let root_dictionary = dxf.new_handle();
+    let group_dictionary = dxf.new_handle();
+    dxf.pair(0, "DICTIONARY");
+    dxf.pair(5, &root_dictionary);
+    dxf.pair(330, 0);
+    dxf.pair(100, "AcDbDictionary");
+    dxf.pair(281, 1);
+    dxf.pair(3, "ACAD_GROUP");
+    dxf.pair(350, &group_dictionary);
+    dxf.pair(0, "DICTIONARY");
+    dxf.pair(5, &group_dictionary);
+    dxf.pair(330, &root_dictionary);
+    dxf.pair(100, "AcDbDictionary");
+    dxf.pair(281, 1);
+    dxf.end_section();
+    dxf.pair(0, "EOF");
+
+    let mut header = DxfWriter::default();
+    header.section("HEADER");
+    header.pair(9, "$ACADVER");
+    header.pair(1, "AC1015");
+    header.pair(9, "$HANDSEED");
+    header.pair(5, dxf.new_handle());
+    header.pair(9, "$INSUNITS");
+    header.pair(70, 4); // Millimeters
+    header.end_section();
+    header.out + &dxf.out
+}
+
+/// Shapes to export from a run: those of one product part, or the `data` shapes when no part is given.
+pub fn select_shapes(part_model: &PartModel, part_index: Option<usize>) -> Result<&[ModelShape], String> {
+    match part_index {
+        Some(index) => part_model
+            .parts
+            .get(index)
+            .map(|part| part.shapes.as_slice())
+            .ok_or_else(|| format!("Part {} does not exist, the run produced {} parts", index, part_model.parts.len())),
+        None => Ok(&part_model.shapes),
+    }
+}
+
+fn write_polyline(dxf: &mut DxfWriter, shape: &ModelShape, owner: &str) {
+    let mut nodes = shape.nodes.as_slice();
+    if nodes.is_empty() {
+        return;
+    }
+
+    // ADekoLib closes shapes by repeating the first node, DXF uses the closed flag instead
+    let closed = shape.is_closed();
+    if closed {
+        nodes = &nodes[..nodes.len() - 1];
+    }
+
+    dxf.entity("LWPOLYLINE", owner, if shape.layer_name.is_empty() { "0" } else { shape.layer_name.as_str() }, false);
+    dxf.pair(100, "AcDbPolyline");
+    dxf.pair(90, nodes.len());
+    dxf.pair(70, if closed { 1 } else { 0 });
+    dxf.pair(38, shape.thickness);
+    for node in nodes {
+        dxf.pair(10, node.x);
+        dxf.pair(20, node.y);
+        if node.bulge != 0.0 {
+            dxf.pair(42, node.bulge);
+        }
+    }
+
+    dxf.pair(1001, DXF_APP_NAME);
+    dxf.pair(1000, format!("face={}", shape.face.map(|face| face.name()).unwrap_or("")));
+    dxf.pair(1040, shape.thickness);
+    if nodes.iter().any(|node| node.z != 0.0) {
+        dxf.pair(1000, "node_z");
+        for node in nodes {
+            dxf.pair(1040, node.z);
+        }
+    }
+}
+
+#[derive(Default)]
+struct DxfWriter {
+    out: String,
+    last_handle: u32,
+}
+
+impl DxfWriter {
+    fn pair(&mut self, code: i32, value: impl Display) {
+        self.out.push_str(&format!("{:>3}\n{}\n", code, value));
+    }
+
+    fn section(&mut self, name: &str) {
+        self.pair(0, "SECTION");
+        self.pair(2, name);
+    }
+
+    fn end_section(&mut self) {
+        self.pair(0, "ENDSEC");
+    }
+
+    /// Hexadecimal handle that no other object of the drawing uses.
+    fn new_handle(&mut self) -> String {
+        self.last_handle += 1;
+        format!("{:X}", self.last_handle)
+    }
+
+    fn handle(&mut self, code: i32) -> String {
+        let handle = self.new_handle();
+        self.pair(code, &handle);
+        handle
+    }
+
+    /// Starts a symbol table and returns its handle, the owner of its records.
+    fn table(&mut self, name: &str, count: usize) -> String {
+        self.pair(0, "TABLE");
+        self.pair(2, name);
+        let handle = self.handle(5);
+        self.pair(330, 0);
+        self.pair(100, "AcDbSymbolTable");
+        self.pair(70, count);
+        handle
+    }
+
+    fn end_table(&mut self) {
+        self.pair(0, "ENDTAB");
+    }
+
+    fn table_entry(&mut self, kind: &str, table: &str, subclass: &str) -> String {
+        self.pair(0, kind);
+        let handle = self.handle(5);
+        self.pair(330, table);
+        self.pair(100, "AcDbSymbolTableRecord");
+        self.pair(100, subclass);
+        handle
+    }
+
+    /// Writes the common entity data up to the layer, the entity subclass follows.
+    fn entity(&mut self, kind: &str, owner: &str, layer: &str, in_paper_space: bool) {
+        self.pair(0, kind);
+        self.handle(5);
+        self.pair(330, owner);
+        self.pair(100, "AcDbEntity");
+        if in_paper_space {
+            self.pair(67, 1);
+        }
+        self.pair(8, layer);
+    }
+}
@@ -0,0 +1,487 @@
+use serde::{Deserialize, Serialize};
+use crate::geometry::{Point, Segment};
+use crate::offset;
+use crate::part_model::{ModelShape, PanelFace, ShapeNode};
+
+// Same tolerance as PPCLib.adeNotEq, coordinates are in mm
+const POSITION_TOLERANCE: f64 = 0.0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArcMode {
+    Relative, // I/J from the arc start to its center
+    Radial,   // R word, arcs over 180 degrees are split in two
+}
+
+/// Post-processor settings, named after the globals PostProcessorCommonsLib.lua reads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostProcessorConfig {
+    pub program_name: String,
+    pub header: Vec<String>, // Written as given, like the newLines of PPCLib.header
+    pub footer: Vec<String>,
+    pub comment_start: String,
+    pub comment_end: String,
+    pub line_number_step: u32, // 0 writes no N words
+    pub extra_zero_in_g_codes: bool,
+    pub extra_zero_in_m_codes: bool,
+    pub toggle_operation_labels: bool,
+    pub tool_no_before_m_code: bool,
+    pub spindle_rpm_before_m_code: bool,
+    pub tool_select_specifier: String,
+    pub arc_mode: ArcMode,
+    pub face: PanelFace, // Only shapes on this face are machined
+    pub safe_z: f64,     // Above the panel surface, which is Z0
+    pub spindle_max: f64,
+}
+
+impl Default for PostProcessorConfig {
+    fn default() -> Self {
+        Self {
+            program_name: String::new(),
+            header: vec!["G17 G21 G90".to_string()],
+            footer: vec!["M30".to_string()],
+            comment_start: "(".to_string(),
+            comment_end: ")".to_string(),
+            line_number_step: 0,
+            extra_zero_in_g_codes: false,
+            extra_zero_in_m_codes: false,
+            toggle_operation_labels: true,
+            tool_no_before_m_code: true,
+            spindle_rpm_before_m_code: false,
+            tool_select_specifier: "T".to_string(),
+            arc_mode: ArcMode::Relative,
+            face: PanelFace::Top,
+            safe_z: 20.0,
+            spindle_max: 22000.0,
+        }
+    }
+}
+
+/// Side of a closed shape the tool cuts on, the tool center is kept a tool radius away from the shape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CutterCompensation {
+    #[default]
+    On, // Tool center on the shape
+    Inside,
+    Outside,
+}
+
+/// Tool and cutting data for the shapes of one layer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolMapping {
+    pub layer_name: String,
+    pub tool_number: u32,
+    pub spindle_speed: f64, // rpm
+    pub clockwise: bool,
+    pub feed_rate: f64,   // mm/min
+    pub plunge_rate: f64, // mm/min
+    pub step_down: f64,   // Depth per pass, 0 cuts the full depth at once
+    #[serde(default)]
+    pub compensation: CutterCompensation,
+    #[serde(default)]
+    pub tool_diameter: Option<f64>, // mm, the diameter in the layer name when not given
+}
+
+impl ToolMapping {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.layer_name.is_empty() {
+            return Err("Tool mapping has no layer name".to_string());
+        }
+        if !(self.spindle_speed >= 0.0 && self.spindle_speed.is_finite()) {
+            return Err(format!("Invalid spindle speed for layer '{}': {}", self.layer_name, self.spindle_speed));
+        }
+        for (name, value) in [("feed rate", self.feed_rate), ("plunge rate", self.plunge_rate)] {
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(format!("Invalid {} for layer '{}': {}", name, self.layer_name, value));
+            }
+        }
+        if !(self.step_down >= 0.0 && self.step_down.is_finite()) {
+            return Err(format!("Invalid step down for layer '{}': {}", self.layer_name, self.step_down));
+        }
+        if let Some(diameter) = self.tool_diameter.filter(|diameter| !(*diameter > 0.0 && diameter.is_finite())) {
+            return Err(format!("Invalid tool diameter for layer '{}': {}", self.layer_name, diameter));
+        }
+        Ok(())
+    }
+}
+
+/// Builds an NC program for the shapes on `config.face` that have a tool mapping for their layer.
+/// Shapes are cut at the depth of their thickness in passes of `step_down`; shapes with node Z values
+/// follow that depth profile instead. The tool center follows the shape, or the shape offset by the
+/// tool radius for inside or outside compensation; no G41/G42 is written. Shapes that are not cut
+/// are listed as comments.
+pub fn generate_gcode(shapes: &[ModelShape], config: &PostProcessorConfig, tools: &[ToolMapping]) -> Result<String, String> {
+    for tool in tools {
+        tool.validate()?;
+    }
+    if !(config.safe_z > 0.0 && config.safe_z.is_finite()) {
+        return Err(format!("Safe Z must be above the panel surface, got {}", config.safe_z));
+    }
+
+    let mut nc = NcWriter::new(config);
+    if config.toggle_operation_labels {
+        nc.comment("--------- HEADER ----------");
+    }
+    if !config.program_name.is_empty() {
+        nc.comment(&config.program_name);
+    }
+    for line in &config.header {
+        nc.line(line);
+    }
+
+    let mut current_tool: Option<&ToolMapping> = None;
+    for (index, shape) in shapes.iter().enumerate() {
+        if shape.nodes.len() < 2 {
+            continue;
+        }
+        if shape.face != Some(config.face) {
+            let face = shape.face.map(|face| format!("on face {}", face.name())).unwrap_or_else(|| "without a face".to_string());
+            nc.comment(&format!("Shape {} on layer {} skipped, {}", index + 1, shape.layer_name, face));
+            continue;
+        }
+        let Some(tool) = tools.iter().find(|tool| tool.layer_name == shape.layer_name) else {
+            nc.comment(&format!("Shape {} on layer {} skipped, no tool mapped", index + 1, shape.layer_name));
+            continue;
+        };
+        let paths = match compensated_paths(shape, tool) {
+            Ok(paths) => paths,
+            Err(reason) => {
+                nc.comment(&format!("Shape {} on layer {} skipped, {}", index + 1, shape.layer_name, reason));
+                continue;
+            }
+        };
+
+        // Controls stop the spindle on M6, it is started again after every tool change
+        let tool_changed = current_tool.map(|t| t.tool_number) != Some(tool.tool_number);
+        if tool_changed {
+            nc.tool_change(tool);
+        }
+        if tool_changed || current_tool.map(|t| (t.spindle_speed, t.clockwise)) != Some((tool.spindle_speed, tool.clockwise)) {
+            nc.spindle(tool);
+        }
+        current_tool = Some(tool);
+
+        if config.toggle_operation_labels {
+            nc.comment(&format!("Shape {} {}", index + 1, shape.layer_name));
+        }
+        for path in &paths {
+            nc.cut_shape(path, tool);
+        }
+    }
+
+    if current_tool.is_some() {
+        nc.rapid_z(config.safe_z);
+        nc.line(&nc.m_code(5));
+    }
+    if config.toggle_operation_labels {
+        nc.comment("--------- FOOTER ----------");
+    }
+    for line in &config.footer {
+        nc.line(line);
+    }
+
+    Ok(nc.out)
+}
+
+// Paths of the tool center for a shape, several when the offset splits it
+fn compensated_paths(shape: &ModelShape, tool: &ToolMapping) -> Result<Vec<ModelShape>, String> {
+    if tool.compensation == CutterCompensation::On {
+        return Ok(vec![shape.clone()]);
+    }
+    if !shape.is_closed() {
+        return Err("open shapes have no inside or outside".to_string());
+    }
+    // The offset polylines have no Z, a depth profile could not follow them
+    if shape.has_depth_profile() {
+        return Err("shapes with a depth profile are not compensated".to_string());
+    }
+    let Some(diameter) = tool.tool_diameter.or(shape.tool_spec.diameter) else {
+        return Err("no tool diameter to compensate with".to_string());
+    };
+
+    // Positive offsets grow counter-clockwise shapes, whose area is positive
+    let polyline = shape.polyline();
+    let outwards = diameter / 2.0 * polyline.area().signum();
+    let distance = if tool.compensation == CutterCompensation::Outside { outwards } else { -outwards };
+    let paths: Vec<ModelShape> = offset::offset_polyline(&polyline, distance)
+        .into_iter()
+        .map(|path| ModelShape {
+            nodes: path.vertices.iter().map(|vertex| ShapeNode { x: vertex.point.x, y: vertex.point.y, z: 0.0, bulge: vertex.bulge }).collect(),
+            ..shape.clone()
+        })
+        .collect();
+    if paths.is_empty() {
+        return Err(format!("too small for a {} mm tool", format_value(diameter)));
+    }
+    Ok(paths)
+}
+
+struct NcWriter<'a> {
+    config: &'a PostProcessorConfig,
+    out: String,
+    line_number: u32,
+    position: [Option<f64>; 3], // Unknown after a tool change
+    feed: Option<f64>,
+}
+
+impl<'a> NcWriter<'a> {
+    fn new(config: &'a PostProcessorConfig) -> Self {
+        Self { config, out: String::new(), line_number: 0, position: [None; 3], feed: None }
+    }
+
+    // PPCLib.lineNumber wraps after N99999
+    fn line(&mut self, text: &str) {
+        if self.config.line_number_step > 0 {
+            self.line_number += self.config.line_number_step;
+            if self.line_number > 99999 {
+                self.line_number = self.config.line_number_step;
+            }
+            self.out.push_str(&format!("N{} ", self.line_number));
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.out.push_str(&format!("{}{}{}\n", self.config.comment_start, text, self.config.comment_end));
+    }
+
+    fn g_code(&self, code: u32) -> String {
+        if self.config.extra_zero_in_g_codes { format!("G{:02}", code) } else { format!("G{}", code) }
+    }
+
+    fn m_code(&self, code: u32) -> String {
+        if self.config.extra_zero_in_m_codes { format!("M{:02}", code) } else { format!("M{}", code) }
+    }
+
+    fn tool_change(&mut self, tool: &ToolMapping) {
+        if self.config.toggle_operation_labels {
+            self.comment("--------- Tool Change ----------");
+        }
+        if self.position[2].is_some() {
+            self.rapid_z(self.config.safe_z);
+        }
+        let select = format!("{}{}", self.config.tool_select_specifier, tool.tool_number);
+        let line = if self.config.tool_no_before_m_code {
+            format!("{} {}", select, self.m_code(6))
+        } else {
+            format!("{} {}", self.m_code(6), select)
+        };
+        self.line(&line);
+        self.position = [None; 3];
+        self.feed = None;
+    }
+
+    fn spindle(&mut self, tool: &ToolMapping) {
+        if tool.spindle_speed == 0.0 {
+            self.line(&self.m_code(5));
+            return;
+        }
+        if tool.spindle_speed > self.config.spindle_max {
+            self.comment("Warning! spindle value is to much than predefined spindle maximum value.");
+        }
+        let speed = format!("S{}", format_value(tool.spindle_speed.min(self.config.spindle_max)));
+        let m_code = self.m_code(if tool.clockwise { 3 } else { 4 });
+        let line = if self.config.spindle_rpm_before_m_code {
+            format!("{} {}", speed, m_code)
+        } else {
+            format!("{} {}", m_code, speed)
+        };
+        self.line(&line);
+    }
+
+    fn cut_shape(&mut self, shape: &ModelShape, tool: &ToolMapping) {
+        let nodes = &shape.nodes;
+        let first = nodes[0];
+        let closed = shape.is_closed();
+
+        let depth = shape.depth();
+        let has_depth_profile = shape.has_depth_profile();
+        let pass_depths: Vec<f64> = if has_depth_profile || tool.step_down == 0.0 || depth <= tool.step_down {
+            vec![depth]
+        } else {
+            let passes = (depth / tool.step_down).ceil() as usize;
+            (1..=passes).map(|pass| (pass as f64 * tool.step_down).min(depth)).collect()
+        };
+        let node_z = |node: &ShapeNode, pass_depth: f64| if has_depth_profile { node.z } else { -pass_depth };
+
+        for (pass, &pass_depth) in pass_depths.iter().enumerate() {
+            // Closed shapes end where they start, the next pass plunges right there
+            if pass == 0 || !closed {
+                self.rapid_z(self.config.safe_z);
+                self.rapid_xy(first.x, first.y);
+            }
+            self.linear([first.x, first.y, node_z(&first, pass_depth)], tool.plunge_rate);
+
+            for pair in nodes.windows(2) {
+                let (from, to) = (pair[0], pair[1]);
+                let end = [to.x, to.y, node_z(&to, pass_depth)];
+                if from.bulge.abs() < 1e-9 {
+                    self.linear(end, tool.feed_rate);
+                } else {
+                    self.arc([from.x, from.y, node_z(&from, pass_depth)], end, from.bulge, tool.feed_rate);
+                }
+            }
+        }
+        self.rapid_z(self.config.safe_z);
+    }
+
+    fn rapid_z(&mut self, z: f64) {
+        let words = self.position_words(None, None, Some(z));
+        if !words.is_empty() {
+            self.line(&format!("{}{}", self.g_code(0), words));
+        }
+    }
+
+    fn rapid_xy(&mut self, x: f64, y: f64) {
+        let words = self.position_words(Some(x), Some(y), None);
+        if !words.is_empty() {
+            self.line(&format!("{}{}", self.g_code(0), words));
+        }
+    }
+
+    fn linear(&mut self, end: [f64; 3], feed: f64) {
+        let words = self.position_words(Some(end[0]), Some(end[1]), Some(end[2]));
+        if !words.is_empty() {
+            let line = format!("{}{}{}", self.g_code(1), words, self.feed_word(feed));
+            self.line(&line);
+        }
+    }
+
+    // Positive bulges run counter-clockwise (G3)
+    fn arc(&mut self, start: [f64; 3], end: [f64; 3], bulge: f64, feed: f64) {
+        let segment = Segment::new(Point::new(start[0], start[1]), Point::new(end[0], end[1]), bulge);
+        let Some(arc) = segment.arc().filter(|_| segment.chord() >= POSITION_TOLERANCE) else {
+            self.linear(end, feed);
+            return;
+        };
+        if self.config.arc_mode == ArcMode::Radial && arc.sweep.abs() > std::f64::consts::PI + 1e-9 {
+            let (first, second) = segment.split(0.5);
+            let middle = [first.end.x, first.end.y, (start[2] + end[2]) / 2.0];
+            self.arc(start, middle, first.bulge, feed);
+            self.arc(middle, end, second.bulge, feed);
+            return;
+        }
+
+        let code = self.g_code(if bulge > 0.0 { 3 } else { 2 });
+        let words = self.position_words(Some(end[0]), Some(end[1]), Some(end[2]));
+        let arc_words = match self.config.arc_mode {
+            ArcMode::Relative => format!(" I{:.3} J{:.3}", arc.center.x - start[0], arc.center.y - start[1]),
+            ArcMode::Radial => format!(" R{:.3}", arc.radius),
+        };
+        let line = format!("{}{}{}{}", code, words, arc_words, self.feed_word(feed));
+        self.line(&line);
+    }
+
+    // Only changed axes are written, all of them after a tool change like PPCLib.goTo
+    fn position_words(&mut self, x: Option<f64>, y: Option<f64>, z: Option<f64>) -> String {
+        let mut words = String::new();
+        for (axis, (name, value)) in [("X", x), ("Y", y), ("Z", z)].into_iter().enumerate() {
+            let Some(value) = value else { continue };
+            if self.position[axis].is_none_or(|current| (current - value).abs() > POSITION_TOLERANCE) {
+                words.push_str(&format!(" {}{:.3}", name, value));
+                self.position[axis] = Some(value);
+            }
+        }
+        words
+    }
+
+    fn feed_word(&mut self, feed: f64) -> String {
+        if self.feed == Some(feed) {
+            return String::new();
+        }
+        self.feed = Some(feed);
+        format!(" F{}", format_value(feed))
+    }
+}
+
+// PPCLib.adeFormat: whole numbers without decimals, others with three
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 { format!("{}", value as i64) } else { format!("{:.3}", value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer_tool::parse_layer_name;
+
+    fn shape(layer_name: &str, thickness: f64, nodes: &[(f64, f64, f64)]) -> ModelShape {
+        ModelShape {
+            layer_name: layer_name.to_string(),
+            thickness,
+            nodes: nodes.iter().map(|&(x, y, bulge)| ShapeNode { x, y, z: 0.0, bulge }).collect(),
+            rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            translation: [0.0; 3],
+            face: Some(PanelFace::Top),
+            tool_spec: parse_layer_name(layer_name),
+        }
+    }
+
+    fn tool(layer_name: &str, tool_number: u32) -> ToolMapping {
+        ToolMapping {
+            layer_name: layer_name.to_string(),
+            tool_number,
+            spindle_speed: 18000.0,
+            clockwise: true,
+            feed_rate: 3000.0,
+            plunge_rate: 1000.0,
+            step_down: 0.0,
+            compensation: CutterCompensation::On,
+            tool_diameter: None,
+        }
+    }
+
+    fn config() -> PostProcessorConfig {
+        PostProcessorConfig { toggle_operation_labels: false, header: Vec::new(), footer: Vec::new(), ..PostProcessorConfig::default() }
+    }
+
+    fn lines(gcode: &str) -> Vec<&str> {
+        gcode.lines().collect()
+    }
+
+    #[test]
+    fn counter_clockwise_bulge_is_g3_around_its_center() {
+        // Half circle below the X axis from (0, 0) to (20, 0)
+        let half_circle = shape("K_Freze6mm", -2.0, &[(0.0, 0.0, 1.0), (20.0, 0.0, 0.0)]);
+        let gcode = generate_gcode(&[half_circle], &config(), &[tool("K_Freze6mm", 1)]).unwrap();
+        assert_eq!(lines(&gcode), [
+            "T1 M6",
+            "M3 S18000",
+            "G0 Z20.000",
+            "G0 X0.000 Y0.000",
+            "G1 Z-2.000 F1000",
+            "G3 X20.000 I10.000 J0.000 F3000",
+            "G0 Z20.000",
+            "M5",
+        ]);
+    }
+
+    #[test]
+    fn pocket_is_cut_inside_in_passes_and_the_spindle_restarts_after_tool_changes() {
+        let pocket = shape("CEP_20mm", -5.0, &[(0.0, 0.0, 0.0), (100.0, 0.0, 0.0), (100.0, 50.0, 0.0), (0.0, 50.0, 0.0), (0.0, 0.0, 0.0)]);
+        let groove = shape("K_Freze6mm", -1.0, &[(10.0, 10.0, 0.0), (90.0, 10.0, 0.0)]);
+        // Same spindle speed and direction for both tools
+        let tools = [ToolMapping { step_down: 2.0, compensation: CutterCompensation::Inside, ..tool("CEP_20mm", 2) }, tool("K_Freze6mm", 1)];
+        let gcode = generate_gcode(&[pocket, groove], &config(), &tools).unwrap();
+        let square = ["G1 X90.000 F3000", "G1 Y40.000", "G1 X10.000", "G1 Y10.000"];
+        let mut expected = vec!["T2 M6", "M3 S18000", "G0 Z20.000", "G0 X10.000 Y10.000"];
+        for depth in ["G1 Z-2.000 F1000", "G1 Z-4.000 F1000", "G1 Z-5.000 F1000"] {
+            expected.push(depth);
+            expected.extend(square);
+        }
+        expected.extend([
+            "G0 Z20.000",
+            "T1 M6",
+            "M3 S18000",
+            "G0 Z20.000",
+            "G0 X10.000 Y10.000",
+            "G1 Z-1.000 F1000",
+            "G1 X90.000 F3000",
+            "G0 Z20.000",
+            "M5",
+        ]);
+        assert_eq!(lines(&gcode), expected);
+    }
+}
@@ -0,0 +1,472 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Bulges and chords below this are a straight segment and a single point
+const EPSILON: f64 = 1e-9;
+
+// Distance between the ends of a polyline below which it is closed
+const CLOSED_TOLERANCE: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn distance(self, other: Point) -> f64 {
+        (other - self).length()
+    }
+
+    pub fn dot(self, other: Point) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Z of the 3D cross product, positive when `other` turns counter-clockwise from `self`.
+    pub fn cross(self, other: Point) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// Rotated 90° counter-clockwise, the left normal of a direction.
+    pub fn perpendicular(self) -> Point {
+        Point::new(-self.y, self.x)
+    }
+
+    pub fn rotated(self, angle: f64) -> Point {
+        let (sin, cos) = angle.sin_cos();
+        Point::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    /// Unit vector in the same direction, the zero vector stays zero.
+    pub fn normalized(self) -> Point {
+        let length = self.length();
+        if length > EPSILON { self * (1.0 / length) } else { Point::default() }
+    }
+
+    pub fn lerp(self, other: Point, t: f64) -> Point {
+        self + (other - self) * t
+    }
+
+    /// Angle of the vector from the X axis, in radians.
+    pub fn angle(self) -> f64 {
+        self.y.atan2(self.x)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Point {
+    type Output = Point;
+    fn mul(self, factor: f64) -> Point {
+        Point::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+    fn neg(self) -> Point {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    pub fn from_point(point: Point) -> Self {
+        Self { min: point, max: point }
+    }
+
+    pub fn include(&mut self, point: Point) {
+        self.min = Point::new(self.min.x.min(point.x), self.min.y.min(point.y));
+        self.max = Point::new(self.max.x.max(point.x), self.max.y.max(point.y));
+    }
+
+    pub fn union(mut self, other: Bounds) -> Bounds {
+        self.include(other.min);
+        self.include(other.max);
+        self
+    }
+}
+
+/// Circular arc from `start_angle` over `sweep` radians, counter-clockwise for a positive sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Arc {
+    pub center: Point,
+    pub radius: f64,
+    pub start_angle: f64,
+    pub sweep: f64,
+}
+
+impl Arc {
+    pub fn point_at_angle(&self, angle: f64) -> Point {
+        self.center + Point::new(angle.cos(), angle.sin()) * self.radius
+    }
+
+    pub fn start(&self) -> Point {
+        self.point_at_angle(self.start_angle)
+    }
+
+    pub fn end(&self) -> Point {
+        self.point_at_angle(self.start_angle + self.sweep)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.radius * self.sweep.abs()
+    }
+
+    /// Whether the arc passes through the direction `angle` from its center.
+    pub fn contains_angle(&self, angle: f64) -> bool {
+        if self.sweep.abs() >= TAU {
+            return true;
+        }
+        let offset = (angle - self.start_angle) * self.sweep.signum();
+        offset.rem_euclid(TAU) <= self.sweep.abs() + EPSILON
+    }
+
+    /// Bounds of the arc itself, its ends and the axis extremes it passes.
+    pub fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::from_point(self.start());
+        bounds.include(self.end());
+        for quadrant in 0..4 {
+            let angle = quadrant as f64 * FRAC_PI_2;
+            if self.contains_angle(angle) {
+                bounds.include(self.point_at_angle(angle));
+            }
+        }
+        bounds
+    }
+
+    /// Bulge of the polyline segment running along this arc, tan(sweep / 4).
+    pub fn bulge(&self) -> f64 {
+        (self.sweep / 4.0).tan()
+    }
+}
+
+/// Polyline segment from `start` to `end`, a straight line or, with a nonzero bulge, an arc.
+/// The bulge is tan(sweep / 4): 1 is a half circle, positive bulges run counter-clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub start: Point,
+    pub end: Point,
+    pub bulge: f64,
+}
+
+impl Segment {
+    pub fn new(start: Point, end: Point, bulge: f64) -> Self {
+        Self { start, end, bulge }
+    }
+
+    pub fn line(start: Point, end: Point) -> Self {
+        Self { start, end, bulge: 0.0 }
+    }
+
+    pub fn from_arc(arc: &Arc) -> Self {
+        Self { start: arc.start(), end: arc.end(), bulge: arc.bulge() }
+    }
+
+    pub fn chord(&self) -> f64 {
+        self.start.distance(self.end)
+    }
+
+    pub fn is_arc(&self) -> bool {
+        self.bulge.abs() > EPSILON && self.chord() > EPSILON
+    }
+
+    /// Signed angle the arc turns through, 0 for a line.
+    pub fn sweep(&self) -> f64 {
+        if self.is_arc() { 4.0 * self.bulge.atan() } else { 0.0 }
+    }
+
+    /// The arc of a bulged segment, `None` for a line. The center lies left of the chord for
+    /// counter-clockwise arcs, (chord / 2) / tan(sweep / 2) away from its middle.
+    pub fn arc(&self) -> Option<Arc> {
+        if !self.is_arc() {
+            return None;
+        }
+        let sweep = self.sweep();
+        let chord = self.end - self.start;
+        let offset = (chord.length() / 2.0) / (sweep / 2.0).tan();
+        let center = self.start.lerp(self.end, 0.5) + chord.normalized().perpendicular() * offset;
+        Some(Arc {
+            center,
+            radius: center.distance(self.start),
+            start_angle: (self.start - center).angle(),
+            sweep,
+        })
+    }
+
+    pub fn length(&self) -> f64 {
+        self.arc().map_or_else(|| self.chord(), |arc| arc.length())
+    }
+
+    /// Point at fraction `t` of the length, 0 at the start and 1 at the end.
+    pub fn point_at(&self, t: f64) -> Point {
+        match self.arc() {
+            Some(arc) => arc.point_at_angle(arc.start_angle + arc.sweep * t),
+            None => self.start.lerp(self.end, t),
+        }
+    }
+
+    /// Unit tangent leaving the start point. Arcs leave the chord half their sweep to the right.
+    pub fn start_direction(&self) -> Point {
+        (self.end - self.start).normalized().rotated(-self.sweep() / 2.0)
+    }
+
+    /// Unit tangent arriving at the end point.
+    pub fn end_direction(&self) -> Point {
+        (self.end - self.start).normalized().rotated(self.sweep() / 2.0)
+    }
+
+    /// The two parts before and after fraction `t` of the length, arcs split into two arcs on the same circle.
+    pub fn split(&self, t: f64) -> (Segment, Segment) {
+        let middle = self.point_at(t);
+        match self.arc() {
+            Some(arc) => {
+                let first = Arc { sweep: arc.sweep * t, ..arc };
+                let second = Arc { start_angle: arc.start_angle + first.sweep, sweep: arc.sweep - first.sweep, ..arc };
+                (Segment::new(self.start, middle, first.bulge()), Segment::new(middle, self.end, second.bulge()))
+            }
+            None => (Segment::line(self.start, middle), Segment::line(middle, self.end)),
+        }
+    }
+
+    /// Fraction of the length at which a point on the line or circle of the segment lies,
+    /// outside 0..1 before the start and past the end.
+    pub fn parameter(&self, point: Point) -> f64 {
+        match self.arc() {
+            Some(arc) => {
+                // Angles are measured from the middle of the arc so both extensions stay close to it
+                let middle = arc.start_angle + arc.sweep / 2.0;
+                let offset = ((point - arc.center).angle() - middle + PI).rem_euclid(TAU) - PI;
+                0.5 + offset / arc.sweep
+            }
+            None => {
+                let chord = self.end - self.start;
+                (point - self.start).dot(chord) / chord.dot(chord)
+            }
+        }
+    }
+
+    /// Nearest point of the segment to `point`.
+    pub fn closest_point(&self, point: Point) -> Point {
+        if self.chord() <= EPSILON {
+            return self.start;
+        }
+        let t = self.parameter(point);
+        if (0.0..=1.0).contains(&t) {
+            return match self.arc() {
+                // Every point of the arc is as near to its center
+                Some(arc) if point.distance(arc.center) <= EPSILON => self.start,
+                Some(arc) => arc.center + (point - arc.center).normalized() * arc.radius,
+                None => self.start.lerp(self.end, t),
+            };
+        }
+        if point.distance(self.start) <= point.distance(self.end) { self.start } else { self.end }
+    }
+
+    /// Points where the lines or circles the two segments lie on meet, beyond the segments too.
+    /// Parallel lines and circles around the same center have none.
+    pub fn extended_intersections(&self, other: &Segment) -> Vec<Point> {
+        match (self.arc(), other.arc()) {
+            (None, None) => line_line_intersection(self.start, self.end, other.start, other.end).into_iter().collect(),
+            (Some(arc), None) => line_circle_intersections(other.start, other.end, arc.center, arc.radius),
+            (None, Some(arc)) => line_circle_intersections(self.start, self.end, arc.center, arc.radius),
+            (Some(a), Some(b)) => circle_circle_intersections(a.center, a.radius, b.center, b.radius),
+        }
+    }
+
+    /// Points the two segments have in common, within `tolerance` of their ends.
+    /// Overlapping parts of the same line or circle are left out.
+    pub fn intersections(&self, other: &Segment, tolerance: f64) -> Vec<Point> {
+        let within = |segment: &Segment, point: Point| {
+            let t = segment.parameter(point);
+            (0.0..=1.0).contains(&t) || point.distance(segment.start) <= tolerance || point.distance(segment.end) <= tolerance
+        };
+        self.extended_intersections(other).into_iter().filter(|&point| within(self, point) && within(other, point)).collect()
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        match self.arc() {
+            Some(arc) => arc.bounds(),
+            None => Bounds::from_point(self.start).union(Bounds::from_point(self.end)),
+        }
+    }
+
+    /// Number of straight pieces that stay within `chord_tolerance` of the arc, 1 for a line.
+    pub fn tessellation_count(&self, chord_tolerance: f64) -> usize {
+        let Some(arc) = self.arc() else {
+            return 1;
+        };
+        // A chord over angle a is radius * (1 - cos(a / 2)) away from the arc at most
+        let max_angle = if chord_tolerance < arc.radius { 2.0 * (1.0 - chord_tolerance / arc.radius).acos() } else { PI };
+        ((arc.sweep.abs() / max_angle.max(EPSILON)).ceil() as usize).max(1)
+    }
+
+    // Signed area between the chord and the arc, positive when the arc bulges to the right
+    fn arc_area(&self) -> f64 {
+        self.arc().map_or(0.0, |arc| {
+            let sweep = arc.sweep.abs();
+            arc.sweep.signum() * arc.radius * arc.radius / 2.0 * (sweep - sweep.sin())
+        })
+    }
+
+    // Whether `point` lies between the chord and the arc
+    fn in_arc_area(&self, point: Point) -> bool {
+        self.arc().is_some_and(|arc| {
+            let side = (self.end - self.start).cross(point - self.start);
+            point.distance(arc.center) < arc.radius && side * arc.sweep < 0.0
+        })
+    }
+}
+
+fn line_line_intersection(a: Point, b: Point, c: Point, d: Point) -> Option<Point> {
+    let (r, s) = (b - a, d - c);
+    let denominator = r.cross(s);
+    if denominator.abs() <= EPSILON * r.length() * s.length() {
+        return None;
+    }
+    Some(a + r * ((c - a).cross(s) / denominator))
+}
+
+// A line touching the circle within EPSILON meets it once
+fn line_circle_intersections(a: Point, b: Point, center: Point, radius: f64) -> Vec<Point> {
+    let direction = (b - a).normalized();
+    if direction == Point::default() {
+        return Vec::new();
+    }
+    let foot = a + direction * (center - a).dot(direction);
+    let distance = foot.distance(center);
+    if distance > radius + EPSILON {
+        return Vec::new();
+    }
+    let half_chord = (radius * radius - distance * distance).max(0.0).sqrt();
+    if half_chord <= EPSILON {
+        return vec![foot];
+    }
+    vec![foot - direction * half_chord, foot + direction * half_chord]
+}
+
+fn circle_circle_intersections(center_a: Point, radius_a: f64, center_b: Point, radius_b: f64) -> Vec<Point> {
+    let distance = center_a.distance(center_b);
+    if distance <= EPSILON || distance > radius_a + radius_b + EPSILON || distance < (radius_a - radius_b).abs() - EPSILON {
+        return Vec::new();
+    }
+    let direction = (center_b - center_a) * (1.0 / distance);
+    let along = (distance * distance + radius_a * radius_a - radius_b * radius_b) / (2.0 * distance);
+    let base = center_a + direction * along;
+    let half_chord = (radius_a * radius_a - along * along).max(0.0).sqrt();
+    if half_chord <= EPSILON {
+        return vec![base];
+    }
+    let across = direction.perpendicular() * half_chord;
+    vec![base + across, base - across]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vertex {
+    pub point: Point,
+    pub bulge: f64, // Bulge of the segment starting at this vertex
+}
+
+/// Polyline in ADekoLib's form: closed ones repeat the first vertex at the end.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Polyline {
+    pub vertices: Vec<Vertex>,
+}
+
+impl Polyline {
+    pub fn new(vertices: Vec<Vertex>) -> Self {
+        Self { vertices }
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        self.vertices.windows(2).map(|pair| Segment::new(pair[0].point, pair[1].point, pair[0].bulge))
+    }
+
+    /// A closed polyline ends where it starts and encloses an area.
+    pub fn is_closed(&self) -> bool {
+        match (self.vertices.first(), self.vertices.last()) {
+            (Some(first), Some(last)) => self.vertices.len() > 2 && first.point.distance(last.point) <= CLOSED_TOLERANCE,
+            _ => false,
+        }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.segments().map(|segment| segment.length()).sum()
+    }
+
+    /// Enclosed area with arcs, positive for counter-clockwise polylines. An open polyline is
+    /// closed with a straight line.
+    pub fn area(&self) -> f64 {
+        let points: Vec<Point> = self.vertices.iter().map(|vertex| vertex.point).collect();
+        let chords: f64 = (0..points.len()).map(|i| points[i].cross(points[(i + 1) % points.len()])).sum();
+        chords / 2.0 + self.segments().map(|segment| segment.arc_area()).sum::<f64>()
+    }
+
+    /// Bounds of the path with its arcs, `None` without vertices.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let first = Bounds::from_point(self.vertices.first()?.point);
+        Some(self.segments().fold(first, |bounds, segment| bounds.union(segment.bounds())))
+    }
+
+    /// Even-odd point-in-polygon test with exact arcs: the point is inside the polygon of the chords,
+    /// flipped for every arc whose area between chord and arc holds it.
+    pub fn contains(&self, point: Point) -> bool {
+        let count = self.vertices.len();
+        if count < 3 {
+            return false;
+        }
+        let mut inside = false;
+        for i in 0..count {
+            let (a, b) = (self.vertices[i].point, self.vertices[(i + 1) % count].point);
+            if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (b.x - a.x) * (point.y - a.y) / (b.y - a.y) {
+                inside = !inside;
+            }
+        }
+        for segment in self.segments() {
+            if segment.in_arc_area(point) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Points along the path, the first vertex included, with arcs split into chords that stay
+    /// within `chord_tolerance` of them.
+    pub fn tessellate(&self, chord_tolerance: f64) -> Vec<Point> {
+        let Some(first) = self.vertices.first() else {
+            return Vec::new();
+        };
+        let mut points = vec![first.point];
+        for segment in self.segments() {
+            let count = segment.tessellation_count(chord_tolerance);
+            points.extend((1..=count).map(|i| segment.point_at(i as f64 / count as f64)));
+        }
+        points
+    }
+}
@@ -0,0 +1,299 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use crate::part_model::PanelFace;
+
+// Tool sizes outside these ranges are part of a layer name for another reason
+const DIAMETER_RANGE: (f64, f64) = (1.0, 50.0);
+const ANGLE_RANGE: (f64, f64) = (15.0, 180.0);
+
+// Strings match the operations of the frontend's LayerAnalysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayerOperation {
+    Grooving,
+    VGrooving,
+    Contouring,
+    InnerContouring,
+    OuterContouring,
+    VCutting,
+    Pocketing,
+    Profiling,
+    Machining,
+    EdgeProfiling,
+    NonMachinable,
+    Prohibited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolShape {
+    Cylindrical,
+    Conical,
+    Ballnose,
+    Radial,
+    Special,
+}
+
+/// Operation and tool a layer name asks for, following the AdekoCAM layer naming conventions:
+/// K = groove along the path, H = contour (IC inner, DIS outer), V = conical tool, CEP = pocket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerToolSpec {
+    pub operation: LayerOperation,
+    pub surface: Option<PanelFace>, // Top or bottom, `None` for layers that are not machined
+    pub tool_shape: Option<ToolShape>,
+    pub diameter: Option<f64>, // mm
+    pub angle: Option<f64>,    // V-bit angle in degrees
+    pub depth: Option<f64>,
+    pub is_inner: bool,
+    pub is_outer: bool,
+}
+
+impl Default for LayerToolSpec {
+    fn default() -> Self {
+        Self {
+            operation: LayerOperation::Machining,
+            surface: Some(PanelFace::Top),
+            tool_shape: Some(ToolShape::Cylindrical),
+            diameter: None,
+            angle: None,
+            depth: None,
+            is_inner: false,
+            is_outer: false,
+        }
+    }
+}
+
+impl LayerToolSpec {
+    fn excluded(operation: LayerOperation) -> Self {
+        Self { operation, surface: None, tool_shape: None, ..Self::default() }
+    }
+
+    pub fn is_machined(&self) -> bool {
+        !matches!(self.operation, LayerOperation::NonMachinable | LayerOperation::Prohibited)
+    }
+}
+
+/// Parses a layer name like `K_Freze10mm`, `H_Ballnose6mm_IC` or `V_Oyuk45` into its tool spec.
+/// Port of the frontend's layerToolDetector.ts, both have to give the same result.
+pub fn parse_layer_name(layer_name: &str) -> LayerToolSpec {
+    let layer = layer_name.trim();
+    if layer.is_empty() {
+        return LayerToolSpec::default();
+    }
+    if is_non_machinable_layer(layer) {
+        return LayerToolSpec::excluded(LayerOperation::NonMachinable);
+    }
+    if is_prohibited_layer(layer) || GENERIC_LAYERS.contains(&layer) {
+        return LayerToolSpec::excluded(LayerOperation::Prohibited);
+    }
+
+    let lower = layer.to_lowercase();
+    let patterns = patterns();
+    let surface = if ["_sf", "bottom", "alt"].iter().any(|s| lower.contains(s)) { PanelFace::Bottom } else { PanelFace::Top };
+    let prefix = patterns.prefix.captures(layer).map(|c| c[1].to_string()).unwrap_or_default();
+    let (tool_shape, operation) = analyze_by_prefix(&prefix, layer, &lower);
+
+    LayerToolSpec {
+        operation,
+        surface: Some(surface),
+        tool_shape: Some(tool_shape),
+        diameter: patterns
+            .diameter_size
+            .iter()
+            .find_map(|pattern| pattern.captures(layer))
+            .and_then(|c| c[1].parse().ok())
+            .or_else(|| first_number(layer, &patterns.diameter, DIAMETER_RANGE)),
+        angle: first_number(layer, &patterns.angle, ANGLE_RANGE),
+        depth: patterns.depth.captures(layer).and_then(|c| c[1].parse().ok()),
+        is_inner: lower.contains("ic") || lower.contains("inner"),
+        is_outer: lower.contains("dis") || lower.contains("outer"),
+    }
+}
+
+fn analyze_by_prefix(prefix: &str, layer: &str, lower: &str) -> (ToolShape, LayerOperation) {
+    use LayerOperation::*;
+    use ToolShape::*;
+
+    match prefix {
+        "K" => {
+            if lower.contains("freze") {
+                (Cylindrical, Grooving)
+            } else if lower.contains("ballnose") {
+                (Ballnose, Grooving)
+            } else if lower.contains("aciliv") {
+                (Conical, VGrooving)
+            } else {
+                (Cylindrical, Grooving)
+            }
+        }
+        "H" => {
+            let inner = lower.contains("ic") || lower.contains("inner");
+            let outer = lower.contains("dis") || lower.contains("outer");
+            let contour = if inner { InnerContouring } else if outer { OuterContouring } else { Contouring };
+            if lower.contains("freze") {
+                (Cylindrical, contour)
+            } else if lower.contains("ballnose") {
+                (Ballnose, contour)
+            } else if lower.contains("desen") {
+                (Special, if outer { OuterContouring } else { Contouring })
+            } else {
+                (Cylindrical, Contouring)
+            }
+        }
+        "V" => (Conical, VCutting),
+        "CEP" => (Cylindrical, Pocketing),
+        "PANEL" => (Cylindrical, Profiling),
+        _ => {
+            let has_number = lower.chars().any(|c| c.is_ascii_digit());
+            // Plain sizes like 20MM name a cylindrical cutter
+            if patterns().millimeters_only.is_match(layer) || ["freze", "roughing", "finishing"].iter().any(|s| lower.contains(s)) {
+                (Cylindrical, Machining)
+            } else if lower.contains("ballnose") {
+                (Ballnose, Machining)
+            } else if ["baliksırti", "balıksırtı"].iter().any(|s| lower.contains(s)) {
+                (Special, Machining)
+            } else if lower.contains("aciliv") && has_number {
+                (Conical, Machining)
+            } else if lower.contains("vgroove") || lower.contains("v_oyuk45") {
+                (Conical, VCutting)
+            } else if lower.contains("oyuk") && has_number {
+                (Cylindrical, Pocketing)
+            } else if ["form", "jnotch", "dovetail"].iter().any(|s| lower.contains(s)) {
+                (Special, Machining)
+            } else if lower.contains("desen") || lower.contains("pattern") {
+                (Ballnose, Machining)
+            } else if lower.contains("edge") && lower.contains("radius") {
+                (Radial, EdgeProfiling)
+            } else {
+                // The frontend's kanal, cizgi and line branches give this result as well
+                (Cylindrical, Machining)
+            }
+        }
+    }
+}
+
+// The first pattern that matches with a value in range wins
+fn first_number(layer: &str, patterns: &[Regex], (min, max): (f64, f64)) -> Option<f64> {
+    patterns
+        .iter()
+        .filter_map(|pattern| pattern.captures(layer))
+        .filter_map(|captures| captures[1].parse::<f64>().ok())
+        .find(|value| (min..=max).contains(value))
+}
+
+// Script and measurement layers (LUA, LMM) and annotations
+fn is_non_machinable_layer(layer: &str) -> bool {
+    let upper = layer.to_uppercase();
+    upper == "LUA"
+        || upper.starts_with("LUA_")
+        || upper.starts_with("LMM")
+        || ["ANNOTATION", "TEXT", "DIMENSION", "MARKUP", "REFERENCE", "GUIDE"].iter().any(|p| upper.contains(p))
+}
+
+fn is_prohibited_layer(layer: &str) -> bool {
+    let upper = layer.to_uppercase();
+    ["CLEANCORNERS", "CLEANUP", "DEEPEND", "DEEPFRAME", "THINFRAME", "V120PENCERE", "VIOLIN", "_TN_", "V120", "V45"]
+        .iter()
+        .any(|p| upper.contains(p))
+}
+
+// Layer names without a size, they say nothing about the tool
+const GENERIC_LAYERS: [&str; 4] = ["K_AciliV", "V_Oyuk", "K_toolType", "AciliV120"];
+
+struct LayerPatterns {
+    prefix: Regex,
+    millimeters_only: Regex,
+    diameter_size: Vec<Regex>, // Explicit sizes, taken whatever the value
+    diameter: Vec<Regex>,
+    angle: Vec<Regex>,
+    depth: Regex,
+}
+
+fn patterns() -> &'static LayerPatterns {
+    static PATTERNS: OnceLock<LayerPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let regex = |pattern: &str| Regex::new(pattern).unwrap();
+        LayerPatterns {
+            prefix: regex(r"^([A-Z]+)_?"),
+            millimeters_only: regex(r"(?i)^(\d+)MM$"),
+            diameter_size: [
+                r"(?i)(\d+)mm",             // 20MM, Freze20mm, TOP_Roughing_10mm
+                r"(?i)TN_(\d+)",            // Tool number
+            ]
+            .iter()
+            .map(|p| regex(p))
+            .collect(),
+            // The frontend's (freze|ballnose|aciliv)(\d+) reads the word instead of the number and
+            // never gives a size, the patterns after it that (\d+)mm already covers are left out too
+            diameter: [
+                r"(?i)(\d+)(?:freze|ballnose)",
+                r"(?i)ballnose_?(\d+)",     // Ballnose_12
+                r"(?i)oyuk(\d+)",           // Oyuk30
+                r"[_\s](\d+)[_\s]",
+                r"^(\d+)",
+                r"(\d+)$",
+            ]
+            .iter()
+            .map(|p| regex(p))
+            .collect(),
+            angle: [
+                r"(?i)aciliv(\d+)",         // K_AciliV90
+                r"(?i)v_?oyuk(\d+)",        // V_Oyuk45
+                r"(?i)oyuk(\d+)",
+                r"(?i)vgroove_?(\d+)",      // TOP_VGroove_90deg
+                r"(?i)^v(\d+)$",            // V45
+                r"(?i)(\d+)(?:deg|°)",
+                r"(?i)v(\d+)",
+                r"(?i)(\d+)v",
+            ]
+            .iter()
+            .map(|p| regex(p))
+            .collect(),
+            depth: regex(r"(?i)d(\d+)"),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LayerOperation::*;
+    use ToolShape::*;
+
+    // Expected values are what layerToolDetector.ts analyzeLayer returns for the same name
+    fn spec(operation: LayerOperation, surface: PanelFace, tool_shape: ToolShape) -> LayerToolSpec {
+        LayerToolSpec { operation, surface: Some(surface), tool_shape: Some(tool_shape), ..LayerToolSpec::default() }
+    }
+
+    #[test]
+    fn matches_the_frontend_detector() {
+        let cases = [
+            ("K_Freze10mm", LayerToolSpec { diameter: Some(10.0), ..spec(Grooving, PanelFace::Top, Cylindrical) }),
+            ("K_AciliV90", LayerToolSpec { angle: Some(90.0), ..spec(VGrooving, PanelFace::Top, Conical) }),
+            ("V_Oyuk45", LayerToolSpec { diameter: Some(45.0), angle: Some(45.0), ..spec(VCutting, PanelFace::Top, Conical) }),
+            (
+                "H_Freze20mm_Ic",
+                LayerToolSpec { diameter: Some(20.0), is_inner: true, ..spec(InnerContouring, PanelFace::Top, Cylindrical) },
+            ),
+            (
+                "H_Freze8mm_D12_DIS",
+                LayerToolSpec { diameter: Some(8.0), depth: Some(12.0), is_outer: true, ..spec(OuterContouring, PanelFace::Top, Cylindrical) },
+            ),
+            // Only the capital C is a prefix, this is not a CEP pocket
+            ("Cep_Acma", spec(Machining, PanelFace::Top, Cylindrical)),
+            ("CEP_20mm", LayerToolSpec { diameter: Some(20.0), ..spec(Pocketing, PanelFace::Top, Cylindrical) }),
+            ("TOP_Ballnose_6mm_SF", LayerToolSpec { diameter: Some(6.0), ..spec(Machining, PanelFace::Bottom, Ballnose) }),
+            ("20MM", LayerToolSpec { diameter: Some(20.0), ..spec(Machining, PanelFace::Top, Cylindrical) }),
+            ("kanal5", LayerToolSpec { diameter: Some(5.0), ..spec(Machining, PanelFace::Top, Cylindrical) }),
+            ("cizgi_3", LayerToolSpec { diameter: Some(3.0), ..spec(Machining, PanelFace::Top, Cylindrical) }),
+            ("", LayerToolSpec::default()),
+            ("LUA", LayerToolSpec::excluded(NonMachinable)),
+            ("V120", LayerToolSpec::excluded(Prohibited)),
+            ("K_AciliV", LayerToolSpec::excluded(Prohibited)),
+        ];
+        for (layer_name, expected) in cases {
+            assert_eq!(parse_layer_name(layer_name), expected, "layer {}", layer_name);
+        }
+    }
+}
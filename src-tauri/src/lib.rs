@@ -0,0 +1,19 @@
+// Model engine shared by the editor and the headless runner
+
+pub mod dxf_export;
+pub mod gcode_export;
+pub mod geometry;
+pub mod layer_tool;
+pub mod lua_engine;
+pub mod lua_error;
+pub mod machining_simulation;
+pub mod model_check;
+pub mod model_script;
+pub mod model_sweep;
+pub mod offset;
+pub mod panel_mesh;
+pub mod part_model;
+pub mod source_map;
+pub mod svg_export;
+pub mod tool_check;
+pub mod tool_library;
@@ -1,34 +1,350 @@
-use mlua::{Lua, Result as LuaResult, Table, Value};
+use mlua::{HookTriggers, Lua, Result as LuaResult, Table, Value};
 use serde::{Deserialize, Serialize};
+use crate::geometry::{Point, Polyline, Vertex};
+use crate::lua_error::{LuaErrorInfo, StackFrame};
+use crate::offset;
+use crate::part_model::{EdgeBand, PartModel};
+use crate::source_map::{SourceLocation, SourceMap, SCRIPT_CHUNK_NAME};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 // Bundle Lua libraries as static strings
 const ADEKO_LIB_LUA: &str = include_str!("../../LIBRARY/luaLibrary/ADekoLib.lua");
 const TURTLE_LUA: &str = include_str!("../../LIBRARY/luaLibrary/turtle.lua");
 const ADEKO_DEBUG_MODE_LUA: &str = include_str!("../../LIBRARY/luaLibrary/ADekoDebugMode.lua");
 
+// Registry key of the doesSizeIncludeEdgeThickness value ADekoLib.start() restores
+const EDGE_THICKNESS_SETTING_KEY: &str = "adeko_does_size_include_edge_thickness";
+
+/// Pen and layer attributes shared by every draw command.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DrawCommand {
-    pub command_type: String,
-    pub x1: f64,
-    pub y1: f64,
-    pub x2: f64,
-    pub y2: f64,
-    pub radius: f64,
+pub struct DrawStyle {
     pub color: String,
     pub size: f64,
-    pub text: String,
     pub layer_name: String,
     pub thickness: Option<f64>, // Thickness/depth information for 3D operations
 }
 
+// Serialized flat with a "command_type" discriminator; coordinate names are the ones the preview reads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command_type", rename_all = "snake_case")]
+pub enum DrawCommand {
+    Line {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        #[serde(flatten)]
+        style: DrawStyle,
+    },
+    // Start/end in degrees as passed to crcl, counter-clockwise on the y-down turtle canvas
+    Arc {
+        x1: f64,
+        y1: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+        #[serde(flatten)]
+        style: DrawStyle,
+    },
+    Circle {
+        x1: f64,
+        y1: f64,
+        radius: f64,
+        #[serde(flatten)]
+        style: DrawStyle,
+    },
+    Rectangle {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        radius: f64, // Corner radius
+        #[serde(flatten)]
+        style: DrawStyle,
+    },
+    Text {
+        x1: f64,
+        y1: f64,
+        text: String,
+        #[serde(flatten)]
+        style: DrawStyle,
+    },
+    Point {
+        x1: f64,
+        y1: f64,
+        #[serde(flatten)]
+        style: DrawStyle,
+    },
+}
+
+impl DrawCommand {
+    pub fn style(&self) -> &DrawStyle {
+        match self {
+            DrawCommand::Line { style, .. }
+            | DrawCommand::Arc { style, .. }
+            | DrawCommand::Circle { style, .. }
+            | DrawCommand::Rectangle { style, .. }
+            | DrawCommand::Text { style, .. }
+            | DrawCommand::Point { style, .. } => style,
+        }
+    }
+}
+
+// Number of VM instructions between two checks of the run limits
+const LIMIT_CHECK_INTERVAL: u32 = 10_000;
+
+// Live batches are flushed when this many new items are pending or this much time has passed
+const BATCH_MAX_ITEMS: usize = 200;
+const BATCH_MAX_INTERVAL: Duration = Duration::from_millis(50);
+
+// Default resource limits of the Lua VM
+const DEFAULT_MEMORY_LIMIT_BYTES: usize = 512 * 1024 * 1024;
+const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    Completed,
+    Failed,
+    TimedOut,
+    InstructionLimitExceeded,
+    Cancelled,
+    ResourceLimitExceeded,
+}
+
+/// Limits applied to the Lua VM for the whole lifetime of an engine.
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceLimits {
+    pub memory_limit_bytes: usize,
+    pub max_call_depth: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            memory_limit_bytes: DEFAULT_MEMORY_LIMIT_BYTES,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+        }
+    }
+}
+
+/// Panel the model is built for, exposed to the script as the ADekoLib globals
+/// X, Y, Z, materialThickness, edge1layer..edge4layer, edge1thickness..edge4thickness, etc.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelParameters {
+    pub x: f64, // Width
+    pub y: f64, // Height
+    pub z: f64, // Depth, used by product models
+    pub material_thickness: f64,
+    pub body_material_thickness: f64,
+    pub door_material_thickness: f64,
+    pub body_material: String, // Product mode materials
+    pub door_material: String,
+    pub edge_bands: [EdgeBand; 4], // edge1..edge4
+    pub model_parameters: String, // "name=value, ..." string parsed by ADekoLib.parseModelParameters
+    pub does_size_include_edge_thickness: bool,
+    pub mdir: Option<u32>, // Model direction in degrees, passed through modelParameters like ADeko does
+}
+
+impl Default for ModelParameters {
+    fn default() -> Self {
+        let edge_band = |index: usize, thickness: f64| EdgeBand {
+            layer_name: format!("LMM{}", index),
+            thickness,
+        };
+
+        Self {
+            x: 500.0,
+            y: 700.0,
+            z: 500.0,
+            material_thickness: 18.0,
+            body_material_thickness: 18.0,
+            door_material_thickness: 18.0,
+            body_material: "Mat Beyaz MDF".to_string(),
+            door_material: "Kestane Orma".to_string(),
+            edge_bands: [edge_band(0, 0.1), edge_band(1, 0.2), edge_band(2, 0.3), edge_band(3, 0.4)],
+            model_parameters: String::new(),
+            does_size_include_edge_thickness: true,
+            mdir: None,
+        }
+    }
+}
+
+impl ModelParameters {
+    /// Defaults of ADekoProductMode.lua, where edge bands are given per part to packIntoPart.
+    pub fn product_defaults() -> Self {
+        let mut parameters = Self::default();
+        for edge_band in parameters.edge_bands.iter_mut() {
+            edge_band.thickness = 0.0;
+        }
+        parameters
+    }
+
+    /// Panel width and height with edge bands, mirroring ADekoLib.makePartShape which adds them
+    /// when the size excludes them.
+    pub fn panel_size(&self) -> (f64, f64) {
+        let [edge1, edge2, edge3, edge4] = &self.edge_bands;
+        if self.does_size_include_edge_thickness {
+            (self.x, self.y)
+        } else {
+            (self.x + edge1.thickness + edge3.thickness, self.y + edge2.thickness + edge4.thickness)
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.x > 0.0 && self.y > 0.0 && self.z > 0.0) {
+            return Err(format!("Invalid panel size {}x{}x{}, all dimensions must be positive", self.x, self.y, self.z));
+        }
+        if !(self.material_thickness > 0.0 && self.body_material_thickness > 0.0 && self.door_material_thickness > 0.0) {
+            return Err("Material thicknesses must be positive".to_string());
+        }
+        if let Some(edge_band) = self.edge_bands.iter().find(|e| e.thickness < 0.0 || e.layer_name.is_empty()) {
+            return Err(format!("Invalid edge band '{}' with thickness {}", edge_band.layer_name, edge_band.thickness));
+        }
+        if let Some(mdir) = self.mdir.filter(|mdir| ![0, 90, 180, 270].contains(mdir)) {
+            return Err(format!("Invalid model direction {}, expected 0, 90, 180 or 270", mdir));
+        }
+        Ok(())
+    }
+
+    // Appends mdir to the parameter string unless the caller already put it there
+    fn model_parameters_string(&self) -> String {
+        match self.mdir {
+            Some(mdir) if !self.model_parameters.contains("mdir") => {
+                if self.model_parameters.trim().is_empty() {
+                    format!("mdir={}", mdir)
+                } else {
+                    format!("{}, mdir={}", self.model_parameters, mdir)
+                }
+            }
+            _ => self.model_parameters.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceLimitKind {
+    Memory,
+    CallDepth,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceLimitError {
+    pub kind: ResourceLimitKind,
+    pub message: String,
+    pub peak_memory_bytes: usize,
+    pub memory_limit_bytes: usize,
+    pub max_call_depth: usize,
+}
+
+/// Incremental output and draw commands produced since the previous batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionBatch {
+    pub sequence: u64,
+    pub output: Vec<String>,
+    pub draw_commands: Vec<DrawCommand>,
+}
+
+pub type BatchSink = Arc<dyn Fn(ExecutionBatch) + Send + Sync>;
+
+// Tracks which part of the output and draw command buffers was already sent to the sink
+struct BatchStream {
+    sink: Option<BatchSink>,
+    sequence: u64,
+    flushed_output: usize,
+    flushed_draw_commands: usize,
+    last_flush: Instant,
+}
+
+impl BatchStream {
+    fn new() -> Self {
+        Self {
+            sink: None,
+            sequence: 0,
+            flushed_output: 0,
+            flushed_draw_commands: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sequence = 0;
+        self.flushed_output = 0;
+        self.flushed_draw_commands = 0;
+        self.last_flush = Instant::now();
+    }
+
+    fn flush(&mut self, output: &[String], draw_commands: &[DrawCommand], force: bool) {
+        let Some(sink) = self.sink.clone() else {
+            return;
+        };
+
+        let pending = (output.len() - self.flushed_output) + (draw_commands.len() - self.flushed_draw_commands);
+        if pending == 0 {
+            return;
+        }
+        if !force && pending < BATCH_MAX_ITEMS && self.last_flush.elapsed() < BATCH_MAX_INTERVAL {
+            return;
+        }
+
+        let batch = ExecutionBatch {
+            sequence: self.sequence,
+            output: output[self.flushed_output..].to_vec(),
+            draw_commands: draw_commands[self.flushed_draw_commands..].to_vec(),
+        };
+        self.sequence += 1;
+        self.flushed_output = output.len();
+        self.flushed_draw_commands = draw_commands.len();
+        self.last_flush = Instant::now();
+        sink(batch);
+    }
+}
+
+// State shared between a running script and its limit hook
+#[derive(Default)]
+struct RunMonitor {
+    interrupted: Mutex<Option<ExecutionStatus>>,
+    resource_limit: Mutex<Option<ResourceLimitKind>>,
+    peak_memory_bytes: AtomicUsize,
+}
+
+/// Limits enforced on a single script run through a Lua instruction hook.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionLimits {
+    pub timeout: Option<Duration>,
+    pub instruction_limit: Option<u64>,
+    pub cancel_flag: Option<Arc<AtomicBool>>,
+}
+
+/// Where a failed run stopped, relative to the partial output and draw commands it returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionStopMarker {
+    pub output_line_index: usize,
+    pub draw_command_index: usize,
+    pub location: Option<SourceLocation>,
+    pub frame: Option<StackFrame>, // Innermost Lua frame that has a line number
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LuaExecutionResult {
     pub success: bool,
+    pub status: ExecutionStatus,
     pub output: String,
     pub error: String,
     pub execution_time_ms: u64,
     pub draw_commands: Vec<DrawCommand>,
+    pub resource_limit: Option<ResourceLimitError>,
+    pub error_details: Option<LuaErrorInfo>,
+    pub stopped_at: Option<ExecutionStopMarker>,
+    pub part_model: Option<PartModel>,
+    // When the run was streamed, output and draw_commands are left empty and only counted here
+    pub streamed: bool,
+    pub output_line_count: usize,
+    pub draw_command_count: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -93,7 +409,11 @@ pub struct NativeLuaEngine {
     #[allow(dead_code)]
     adeko_state: Arc<Mutex<AdekoState>>,
     draw_commands: Arc<Mutex<Vec<DrawCommand>>>,
+    batch_stream: Arc<Mutex<BatchStream>>,
+    open_output_line: Arc<Mutex<Option<usize>>>, // Index of the line io.write is still appending to
+    source_map: Mutex<Option<SourceMap>>,
     lua_library_path: Option<String>,
+    resource_limits: ResourceLimits,
 }
 
 impl NativeLuaEngine {
@@ -103,12 +423,20 @@ impl NativeLuaEngine {
     }
 
     pub fn new_with_library_path(lua_library_path: Option<String>) -> LuaResult<Self> {
-        println!("=== CREATING NEW LUA ENGINE ===");
-        println!("Library path: {:?}", lua_library_path);
+        Self::new_with_resource_limits(lua_library_path, ResourceLimits::default())
+    }
+
+    pub fn new_with_resource_limits(lua_library_path: Option<String>, resource_limits: ResourceLimits) -> LuaResult<Self> {
+        eprintln!("=== CREATING NEW LUA ENGINE ===");
+        eprintln!("Library path: {:?}", lua_library_path);
+        eprintln!("Resource limits: {:?}", resource_limits);
 
         // Create Lua with standard libraries including debug
         let lua = Lua::new();
 
+        // Cap the memory of the VM so runaway table growth fails instead of eating all RAM
+        lua.set_memory_limit(resource_limits.memory_limit_bytes)?;
+
         // Enable debug library for turtle.lua compatibility
         lua.load(r#"
             -- Enable debug library functions that turtle.lua needs
@@ -128,6 +456,7 @@ impl NativeLuaEngine {
         let turtle_state = Arc::new(Mutex::new(TurtleState::default()));
         let adeko_state = Arc::new(Mutex::new(AdekoState::default()));
         let draw_commands = Arc::new(Mutex::new(Vec::new()));
+        let batch_stream = Arc::new(Mutex::new(BatchStream::new()));
 
         let engine = Self {
             lua,
@@ -135,16 +464,30 @@ impl NativeLuaEngine {
             turtle_state,
             adeko_state,
             draw_commands,
+            batch_stream,
+            open_output_line: Arc::new(Mutex::new(None)),
+            source_map: Mutex::new(None),
             lua_library_path,
+            resource_limits,
         };
 
-        println!("=== SETTING UP LUA ENVIRONMENT ===");
+        eprintln!("=== SETTING UP LUA ENVIRONMENT ===");
         engine.setup_lua_environment()?;
-        println!("=== FINISHED SETTING UP LUA ENVIRONMENT ===");
+        eprintln!("=== FINISHED SETTING UP LUA ENVIRONMENT ===");
 
         Ok(engine)
     }
 
+    /// Streams output and draw commands to `sink` in batches while a script runs.
+    pub fn set_batch_sink(&self, sink: BatchSink) {
+        self.batch_stream.lock().unwrap().sink = Some(sink);
+    }
+
+    /// Maps error line numbers of the next runs back to the segments of an assembled script.
+    pub fn set_source_map(&self, source_map: SourceMap) {
+        *self.source_map.lock().unwrap() = Some(source_map);
+    }
+
     fn setup_lua_environment(&self) -> LuaResult<()> {
         let globals = self.lua.globals();
 
@@ -156,6 +499,9 @@ impl NativeLuaEngine {
 
         // Setup custom print function
         let output_buffer = Arc::clone(&self.output_buffer);
+        let open_output_line = Arc::clone(&self.open_output_line);
+        let draw_commands = Arc::clone(&self.draw_commands);
+        let batch_stream = Arc::clone(&self.batch_stream);
         let print_fn = self.lua.create_function(move |_, args: mlua::Variadic<Value>| {
             let mut output = output_buffer.lock().unwrap();
             let line = args
@@ -170,35 +516,101 @@ impl NativeLuaEngine {
                 })
                 .collect::<Vec<_>>()
                 .join("\t");
-            output.push(line);
+            // print completes a line started by io.write, like it would on a terminal
+            write_output(&mut output, &mut open_output_line.lock().unwrap(), &format!("{}\n", line));
+            batch_stream.lock().unwrap().flush(&output, &draw_commands.lock().unwrap(), false);
             Ok(())
         })?;
         globals.set("print", print_fn)?;
 
+        // Redirect io.write and io.stdout:write into the output buffer,
+        // ADekoLib reports warnings such as clashing model nodes through them
+        self.setup_io_write(&globals)?;
+
         // Load bundled Lua libraries
         self.load_bundled_lua_libraries(&globals)?;
 
         Ok(())
     }
 
+    fn setup_io_write(&self, globals: &Table) -> LuaResult<()> {
+        let output_buffer = Arc::clone(&self.output_buffer);
+        let open_output_line = Arc::clone(&self.open_output_line);
+        let draw_commands = Arc::clone(&self.draw_commands);
+        let batch_stream = Arc::clone(&self.batch_stream);
+        let write_fn = self.lua.create_function(move |_, args: mlua::Variadic<mlua::String>| {
+            let mut output = output_buffer.lock().unwrap();
+            let mut open_line = open_output_line.lock().unwrap();
+            for arg in args.iter() {
+                write_output(&mut output, &mut open_line, &arg.to_string_lossy());
+            }
+
+            // The open line can still grow, so only complete lines are streamed
+            let complete_lines = output.len() - usize::from(open_line.is_some());
+            batch_stream.lock().unwrap().flush(&output[..complete_lines], &draw_commands.lock().unwrap(), false);
+            Ok(())
+        })?;
+
+        let io: Table = globals.get("io")?;
+        let stdout: Value = io.get("stdout")?;
+        self.lua.load(r#"
+            local write_output, stdout = ...
+            io.write = function(...)
+                write_output(...)
+                return io.stdout
+            end
+            -- Proxy io.stdout so that write goes to the output buffer and everything else to the real file
+            io.stdout = setmetatable({}, {
+                __index = function(_, key)
+                    if key == "write" then
+                        return function(self, ...)
+                            write_output(...)
+                            return self
+                        end
+                    end
+                    local value = stdout[key]
+                    if type(value) == "function" then
+                        return function(_, ...) return value(stdout, ...) end
+                    end
+                    return value
+                end
+            })
+        "#).set_name("=io_redirect").call::<_, ()>((write_fn, stdout))?;
+
+        Ok(())
+    }
+
     fn load_bundled_lua_libraries(&self, globals: &Table) -> LuaResult<()> {
-        println!("=== LOADING BUNDLED LUA LIBRARIES ===");
+        eprintln!("=== LOADING BUNDLED LUA LIBRARIES ===");
 
         // Load ADekoLib.lua as a module and set it globally
-        println!("Loading bundled ADekoLib.lua ({} bytes)", ADEKO_LIB_LUA.len());
+        eprintln!("Loading bundled ADekoLib.lua ({} bytes)", ADEKO_LIB_LUA.len());
 
         // Execute the module and capture the returned ADekoLib table
-        let adeko_lib_table: Value = self.lua.load(ADEKO_LIB_LUA).call(())?;
-        globals.set("ADekoLib", adeko_lib_table)?;
+        let adeko_lib_table: Value = self.lua.load(ADEKO_LIB_LUA).set_name("=ADekoLib.lua").call(())?;
+        globals.set("ADekoLib", adeko_lib_table.clone())?;
 
-        println!("✓ ADekoLib.lua loaded successfully (start() will be called before script execution)");
+        // ADekoLib.start() resets doesSizeIncludeEdgeThickness to "true", put the panel's setting back after it
+        let edge_thickness_setting = self.lua.create_function(|lua, ()| {
+            lua.named_registry_value::<Value>(EDGE_THICKNESS_SETTING_KEY)
+        })?;
+        self.lua.load(r#"
+            local ADekoLib, edge_thickness_setting = ...
+            local start = ADekoLib.start
+            ADekoLib.start = function(...)
+                start(...)
+                doesSizeIncludeEdgeThickness = edge_thickness_setting() or doesSizeIncludeEdgeThickness
+            end
+        "#).set_name("=ADekoLib_start").call::<_, ()>((adeko_lib_table, edge_thickness_setting))?;
+
+        eprintln!("✓ ADekoLib.lua loaded successfully (start() will be called before script execution)");
 
         // Load turtle.lua (but don't override our built-in turtle functions)
-        println!("Loading bundled turtle.lua ({} bytes)", TURTLE_LUA.len());
+        eprintln!("Loading bundled turtle.lua ({} bytes)", TURTLE_LUA.len());
         // We'll load it but our built-in turtle functions will take precedence
-        self.lua.load(TURTLE_LUA).exec().unwrap_or_else(|e| {
-            println!("⚠ Warning: Could not load turtle.lua: {}", e);
-            println!("Using built-in turtle functions instead");
+        self.lua.load(TURTLE_LUA).set_name("=turtle.lua").exec().unwrap_or_else(|e| {
+            eprintln!("⚠ Warning: Could not load turtle.lua: {}", e);
+            eprintln!("Using built-in turtle functions instead");
         });
 
         // Setup turtle graphics functions (these will override any from turtle.lua)
@@ -206,11 +618,14 @@ impl NativeLuaEngine {
 
         // Don't load ADekoDebugMode.lua here - it will be loaded when required
         // because it immediately tries to execute modelMain() which doesn't exist yet
-        println!("ADekoDebugMode.lua will be loaded when required by user script");
+        eprintln!("ADekoDebugMode.lua will be loaded when required by user script");
 
         // Setup debug mode variables
         self.setup_debug_variables(&globals)?;
 
+        // Native versions of ADekoLib helpers, library code can switch to them
+        self.setup_native_functions(globals)?;
+
         // Mark modules as loaded in package.loaded
         let package: Table = globals.get("package")?;
         let loaded: Table = package.get("loaded")?;
@@ -220,7 +635,7 @@ impl NativeLuaEngine {
         loaded.set("turtle", Value::Boolean(true))?;
         // Don't mark ADekoDebugMode as loaded yet - it will be loaded when required
 
-        println!("✓ All bundled Lua libraries loaded successfully");
+        eprintln!("✓ All bundled Lua libraries loaded successfully");
         Ok(())
     }
 
@@ -249,14 +664,14 @@ impl NativeLuaEngine {
         let lua_library_path = self.lua_library_path.clone();
 
         let custom_require = self.lua.create_function(move |lua, module_name: String| {
-            println!("Custom require called for module: {}", module_name);
+            eprintln!("Custom require called for module: {}", module_name);
             let package: Table = lua.globals().get("package")?;
             let loaded: Table = package.get("loaded")?;
 
             // Check if module is already loaded
             if let Ok(module) = loaded.get::<_, Value>(module_name.clone()) {
                 if !matches!(module, Value::Nil) {
-                    println!("Module {} already loaded", module_name);
+                    eprintln!("Module {} already loaded", module_name);
                     return Ok(module);
                 }
             }
@@ -264,36 +679,36 @@ impl NativeLuaEngine {
             // Special handling for bundled modules
             match module_name.as_str() {
                 "ADekoLib" => {
-                    println!("ADekoLib module requested - checking if already loaded globally");
+                    eprintln!("ADekoLib module requested - checking if already loaded globally");
                     let adeko_lib = lua.globals().get::<_, Value>("ADekoLib")?;
                     if !matches!(adeko_lib, Value::Nil) {
                         loaded.set(module_name.clone(), adeko_lib.clone())?;
-                        println!("✓ ADekoLib module found globally and returned");
+                        eprintln!("✓ ADekoLib module found globally and returned");
                         return Ok(adeko_lib);
                     }
 
                     // If not found, this shouldn't happen since we load it at startup
-                    println!("⚠ Warning: ADekoLib not found globally, this shouldn't happen");
+                    eprintln!("⚠ Warning: ADekoLib not found globally, this shouldn't happen");
                     return Err(mlua::Error::RuntimeError("ADekoLib should be loaded globally".to_string()));
                 }
                 "turtle" => {
-                    println!("Turtle module requested - using built-in functions");
+                    eprintln!("Turtle module requested - using built-in functions");
                     let result = Value::Boolean(true);
                     loaded.set(module_name.clone(), result.clone())?;
-                    println!("✓ Built-in turtle module loaded successfully");
+                    eprintln!("✓ Built-in turtle module loaded successfully");
                     return Ok(result);
                 }
                 "ADekoDebugMode" => {
-                    println!("ADekoDebugMode module requested - loading bundled version");
+                    eprintln!("ADekoDebugMode module requested - loading bundled version");
                     // Load ADekoDebugMode.lua now (when it's actually needed)
-                    lua.load(ADEKO_DEBUG_MODE_LUA).exec()?;
+                    lua.load(ADEKO_DEBUG_MODE_LUA).set_name("=ADekoDebugMode.lua").exec()?;
                     let result = Value::Boolean(true);
                     loaded.set(module_name.clone(), result.clone())?;
-                    println!("✓ ADekoDebugMode module loaded successfully");
+                    eprintln!("✓ ADekoDebugMode module loaded successfully");
                     return Ok(result);
                 }
                 _ => {
-                    println!("Module {} not found in bundled modules", module_name);
+                    eprintln!("Module {} not found in bundled modules", module_name);
                 }
             }
 
@@ -304,7 +719,9 @@ impl NativeLuaEngine {
                 if module_file.exists() {
                     if let Ok(content) = fs::read_to_string(&module_file) {
                         // For other modules, execute and return the result
-                        let result = lua.load(&content).call::<_, Value>(())?;
+                        let result = lua.load(&content)
+                            .set_name(format!("={}.lua", module_name))
+                            .call::<_, Value>(())?;
                         loaded.set(module_name.clone(), result.clone())?;
                         return Ok(result);
                     }
@@ -326,19 +743,21 @@ impl NativeLuaEngine {
         let output_buffer = Arc::clone(&self.output_buffer);
         let draw_commands = Arc::clone(&self.draw_commands);
         let adeko_state = Arc::clone(&self.adeko_state);
+        let batch_stream = Arc::clone(&self.batch_stream);
 
         // move function
         let turtle_state_clone = Arc::clone(&turtle_state);
         let output_buffer_clone = Arc::clone(&output_buffer);
         let draw_commands_clone = Arc::clone(&draw_commands);
         let adeko_state_clone = Arc::clone(&adeko_state);
+        let batch_stream_clone = Arc::clone(&batch_stream);
         let lua_ref = &self.lua;
         let move_fn = lua_ref.create_function(move |lua, distance: f64| {
             let mut state = turtle_state_clone.lock().unwrap();
             let mut output = output_buffer_clone.lock().unwrap();
             let mut commands = draw_commands_clone.lock().unwrap();
 
-            println!("Turtle move called with distance: {}", distance);
+            eprintln!("Turtle move called with distance: {}", distance);
 
             let new_x = state.x + distance * state.angle.to_radians().cos();
             let new_y = state.y + distance * state.angle.to_radians().sin();
@@ -352,20 +771,19 @@ impl NativeLuaEngine {
                 let current_layer: String = globals.get("currentLayerName").unwrap_or_else(|_| "default".to_string());
                 let current_thickness: Option<f64> = globals.get("currentThickness").ok();
 
-                commands.push(DrawCommand {
-                    command_type: "line".to_string(),
+                commands.push(DrawCommand::Line {
                     x1: state.x,
                     y1: state.y,
                     x2: new_x,
                     y2: new_y,
-                    radius: 0.0,
-                    color: state.pen_color.clone(),
-                    size: state.pen_size,
-                    text: String::new(),
-                    layer_name: current_layer,
-                    thickness: current_thickness,
+                    style: DrawStyle {
+                        color: state.pen_color.clone(),
+                        size: state.pen_size,
+                        layer_name: current_layer,
+                        thickness: current_thickness,
+                    },
                 });
-                println!("Added line draw command, total commands: {}", commands.len());
+                eprintln!("Added line draw command, total commands: {}", commands.len());
             } else {
                 output.push(format!("Moving from ({:.2}, {:.2}) to ({:.2}, {:.2})",
                     state.x, state.y, new_x, new_y));
@@ -373,6 +791,7 @@ impl NativeLuaEngine {
 
             state.x = new_x;
             state.y = new_y;
+            batch_stream_clone.lock().unwrap().flush(&output, &commands, false);
             Ok(())
         })?;
         globals.set("move", move_fn)?;
@@ -398,7 +817,7 @@ impl NativeLuaEngine {
             let mut output = output_buffer_clone.lock().unwrap();
 
             state.pen_down = true;
-            println!("Pen down called - pen_down state: {}", state.pen_down);
+            eprintln!("Pen down called - pen_down state: {}", state.pen_down);
             output.push("Pen down".to_string());
             Ok(())
         })?;
@@ -523,18 +942,16 @@ impl NativeLuaEngine {
             let current_layer: String = globals.get("currentLayerName").unwrap_or_else(|_| "default".to_string());
 
             // Add draw command for text
-            commands.push(DrawCommand {
-                command_type: "text".to_string(),
+            commands.push(DrawCommand::Text {
                 x1: x,
                 y1: y,
-                x2: 0.0,
-                y2: 0.0,
-                radius: 0.0,
-                color: state.pen_color.clone(),
-                size: state.pen_size,
                 text: text_content,
-                layer_name: current_layer,
-                thickness: None, // Text doesn't have thickness
+                style: DrawStyle {
+                    color: state.pen_color.clone(),
+                    size: state.pen_size,
+                    layer_name: current_layer,
+                    thickness: None, // Text doesn't have thickness
+                },
             });
 
             Ok(())
@@ -551,40 +968,51 @@ impl NativeLuaEngine {
         })?;
         globals.set("open", open_fn)?;
 
-        // crcl function (circle)
+        // crcl function (circle) - matches turtle.lua signature: crcl(x, y, r, color, start, finish);
+        // ADekoLib.polylineimp passes start/finish angles to draw bulge segments as arcs
         let output_buffer_clone = Arc::clone(&output_buffer);
         let draw_commands_clone = Arc::clone(&draw_commands);
         let turtle_state_clone = Arc::clone(&turtle_state);
         let adeko_state_clone = Arc::clone(&adeko_state);
+        let batch_stream_clone = Arc::clone(&batch_stream);
         let lua_ref = &self.lua;
-        let crcl_fn = lua_ref.create_function(move |lua, (x, y, radius): (f64, f64, f64)| {
+        let crcl_fn = lua_ref.create_function(move |lua, (x, y, radius, _color, start, finish): (f64, f64, f64, Value, Option<f64>, Option<f64>)| {
             let mut output = output_buffer_clone.lock().unwrap();
             let mut commands = draw_commands_clone.lock().unwrap();
             let state = turtle_state_clone.lock().unwrap();
 
-            println!("Turtle crcl called with x: {}, y: {}, radius: {}", x, y, radius);
-            output.push(format!("Circle at ({:.2}, {:.2}) with radius {:.2}", x, y, radius));
+            // Like turtle.lua, a missing start defaults to 0 and a missing finish to 360
+            let is_arc = start.is_some() || finish.is_some();
+            let start_angle = start.unwrap_or(0.0);
+            let end_angle = finish.unwrap_or(360.0);
+
+            eprintln!("Turtle crcl called with x: {}, y: {}, radius: {}, start: {:?}, finish: {:?}", x, y, radius, start, finish);
+            if is_arc {
+                output.push(format!("Arc at ({:.2}, {:.2}) with radius {:.2} from {:.2} to {:.2} degrees",
+                    x, y, radius, start_angle, end_angle));
+            } else {
+                output.push(format!("Circle at ({:.2}, {:.2}) with radius {:.2}", x, y, radius));
+            }
 
             // Get current layer and thickness from global variables
             let globals = lua.globals();
             let current_layer: String = globals.get("currentLayerName").unwrap_or_else(|_| "default".to_string());
             let current_thickness: Option<f64> = globals.get("currentThickness").ok();
 
-            // Add draw command for circle
-            commands.push(DrawCommand {
-                command_type: "circle".to_string(),
-                x1: x,
-                y1: y,
-                x2: 0.0,
-                y2: 0.0,
-                radius,
+            // Add draw command for circle or arc
+            let style = DrawStyle {
                 color: state.pen_color.clone(),
                 size: state.pen_size,
-                text: String::new(),
                 layer_name: current_layer,
                 thickness: current_thickness,
+            };
+            commands.push(if is_arc {
+                DrawCommand::Arc { x1: x, y1: y, radius, start_angle, end_angle, style }
+            } else {
+                DrawCommand::Circle { x1: x, y1: y, radius, style }
             });
-            println!("Added circle draw command, total commands: {}", commands.len());
+            eprintln!("Added circle draw command, total commands: {}", commands.len());
+            batch_stream_clone.lock().unwrap().flush(&output, &commands, false);
             Ok(())
         })?;
         globals.set("crcl", crcl_fn)?;
@@ -594,6 +1022,7 @@ impl NativeLuaEngine {
         let draw_commands_clone = Arc::clone(&draw_commands);
         let turtle_state_clone = Arc::clone(&turtle_state);
         let adeko_state_clone = Arc::clone(&adeko_state);
+        let batch_stream_clone = Arc::clone(&batch_stream);
         let lua_ref = &self.lua;
         let line_fn = lua_ref.create_function(move |lua, (x1, y1, x2, y2): (f64, f64, f64, f64)| {
             let mut output = output_buffer_clone.lock().unwrap();
@@ -608,19 +1037,19 @@ impl NativeLuaEngine {
             let current_thickness: Option<f64> = globals.get("currentThickness").ok();
 
             // Add draw command for line
-            commands.push(DrawCommand {
-                command_type: "line".to_string(),
+            commands.push(DrawCommand::Line {
                 x1,
                 y1,
                 x2,
                 y2,
-                radius: 0.0,
-                color: state.pen_color.clone(),
-                size: state.pen_size,
-                text: String::new(),
-                layer_name: current_layer,
-                thickness: current_thickness,
+                style: DrawStyle {
+                    color: state.pen_color.clone(),
+                    size: state.pen_size,
+                    layer_name: current_layer,
+                    thickness: current_thickness,
+                },
             });
+            batch_stream_clone.lock().unwrap().flush(&output, &commands, false);
             Ok(())
         })?;
         globals.set("line", line_fn)?;
@@ -630,6 +1059,7 @@ impl NativeLuaEngine {
         let draw_commands_clone = Arc::clone(&draw_commands);
         let turtle_state_clone = Arc::clone(&turtle_state);
         let adeko_state_clone = Arc::clone(&adeko_state);
+        let batch_stream_clone = Arc::clone(&batch_stream);
         let lua_ref = &self.lua;
         let rect_fn = lua_ref.create_function(move |lua, args: mlua::Variadic<f64>| {
             let mut output = output_buffer_clone.lock().unwrap();
@@ -651,19 +1081,20 @@ impl NativeLuaEngine {
             let current_thickness: Option<f64> = globals.get("currentThickness").ok();
 
             // Add draw command for rectangle
-            commands.push(DrawCommand {
-                command_type: "rectangle".to_string(),
+            commands.push(DrawCommand::Rectangle {
                 x1: x,
                 y1: y,
                 x2: x + width,
                 y2: y + height,
                 radius: corner_radius,
-                color: state.pen_color.clone(),
-                size: state.pen_size,
-                text: String::new(),
-                layer_name: current_layer,
-                thickness: current_thickness,
+                style: DrawStyle {
+                    color: state.pen_color.clone(),
+                    size: state.pen_size,
+                    layer_name: current_layer,
+                    thickness: current_thickness,
+                },
             });
+            batch_stream_clone.lock().unwrap().flush(&output, &commands, false);
             Ok(())
         })?;
         globals.set("rect", rect_fn)?;
@@ -701,19 +1132,16 @@ impl NativeLuaEngine {
             let current_layer: String = globals.get("currentLayerName").unwrap_or_else(|_| "default".to_string());
             let current_thickness: Option<f64> = globals.get("currentThickness").ok();
 
-            // Add draw command for a small circle to represent a pixel
-            commands.push(DrawCommand {
-                command_type: "circle".to_string(),
+            // Add draw command for the pixel
+            commands.push(DrawCommand::Point {
                 x1: state.x + x,
                 y1: state.y + y,
-                x2: 0.0,
-                y2: 0.0,
-                radius: 1.0, // Small radius for pixel
-                color: state.pen_color.clone(),
-                size: state.pen_size,
-                text: String::new(),
-                layer_name: current_layer,
-                thickness: current_thickness,
+                style: DrawStyle {
+                    color: state.pen_color.clone(),
+                    size: state.pen_size,
+                    layer_name: current_layer,
+                    thickness: current_thickness,
+                },
             });
             Ok(())
         })?;
@@ -930,96 +1358,374 @@ impl NativeLuaEngine {
         Ok(())
     }
 
+    fn setup_native_functions(&self, globals: &Table) -> LuaResult<()> {
+        let native = self.lua.create_table()?;
+
+        // ADekoNative.offset(points, value) takes the points of ADekoLib.offSet, {x, y, z, bulge},
+        // and returns a list of loops in the same form
+        let offset_fn = self.lua.create_function(|lua, (points, value): (Table, f64)| {
+            let mut vertices = Vec::new();
+            for point in points.sequence_values::<Table>() {
+                let point = point?;
+                vertices.push(Vertex {
+                    point: Point::new(point.get(1)?, point.get(2)?),
+                    bulge: point.get::<_, Option<f64>>(4)?.unwrap_or_default(),
+                });
+            }
+
+            let loops = lua.create_table()?;
+            for result in offset::offset_polyline(&Polyline::new(vertices), value) {
+                let loop_table = lua.create_table()?;
+                for vertex in result.vertices {
+                    loop_table.push(lua.create_sequence_from([vertex.point.x, vertex.point.y, 0.0, vertex.bulge])?)?;
+                }
+                loops.push(loop_table)?;
+            }
+            Ok(loops)
+        })?;
+        native.set("offset", offset_fn)?;
+
+        globals.set("ADekoNative", native)?;
+        Ok(())
+    }
+
     fn setup_debug_variables(&self, globals: &Table) -> LuaResult<()> {
-        // ADekoDebugMode variables
-        globals.set("X", 500.0)?;
-        globals.set("Y", 700.0)?;
-        globals.set("modelParameters", "")?;
-        globals.set("materialThickness", 18.0)?;
+        // ADekoDebugMode variables, replaced by set_model_parameters before a parameterized run
+        self.apply_model_parameters(globals, &ModelParameters::default())?;
         globals.set("offset", 20.0)?;
-        globals.set("edge1layer", "LMM0")?;
-        globals.set("edge2layer", "LMM1")?;
-        globals.set("edge3layer", "LMM2")?;
-        globals.set("edge4layer", "LMM3")?;
-        globals.set("edge1thickness", 0.1)?;
-        globals.set("edge2thickness", 0.2)?;
-        globals.set("edge3thickness", 0.3)?;
-        globals.set("edge4thickness", 0.4)?;
-        globals.set("doesSizeIncludeEdgeThickness", "false")?;
         globals.set("currentLayerName", "LUA")?;
         globals.set("currentThickness", 0.0)?; // Initialize current thickness
 
         Ok(())
     }
 
+    /// Sets the panel globals the next runs build the model for.
+    pub fn set_model_parameters(&self, parameters: &ModelParameters) -> LuaResult<()> {
+        self.apply_model_parameters(&self.lua.globals(), parameters)
+    }
+
+    fn apply_model_parameters(&self, globals: &Table, parameters: &ModelParameters) -> LuaResult<()> {
+        globals.set("X", parameters.x)?;
+        globals.set("Y", parameters.y)?;
+        globals.set("Z", parameters.z)?;
+        globals.set("materialThickness", parameters.material_thickness)?;
+        globals.set("bodyMaterialThickness", parameters.body_material_thickness)?;
+        globals.set("doorMaterialThickness", parameters.door_material_thickness)?;
+        globals.set("bodyMaterial", parameters.body_material.as_str())?;
+        globals.set("doorMaterial", parameters.door_material.as_str())?;
+        for (index, edge_band) in parameters.edge_bands.iter().enumerate() {
+            globals.set(format!("edge{}layer", index + 1), edge_band.layer_name.as_str())?;
+            globals.set(format!("edge{}thickness", index + 1), edge_band.thickness)?;
+        }
+        globals.set("modelParameters", parameters.model_parameters_string())?;
+        let does_size_include_edge_thickness = parameters.does_size_include_edge_thickness.to_string();
+        globals.set("doesSizeIncludeEdgeThickness", does_size_include_edge_thickness.as_str())?;
+        self.lua.set_named_registry_value(EDGE_THICKNESS_SETTING_KEY, does_size_include_edge_thickness)?;
+        globals.set("mdir", parameters.mdir)?;
+
+        Ok(())
+    }
+
+    fn install_limit_hook(&self, limits: &ExecutionLimits, monitor: Arc<RunMonitor>) {
+        let deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        let instruction_limit = limits.instruction_limit;
+        let cancel_flag = limits.cancel_flag.clone();
+        let max_call_depth = self.resource_limits.max_call_depth;
+        let executed = AtomicU64::new(0);
+
+        let triggers = HookTriggers::new().every_nth_instruction(LIMIT_CHECK_INTERVAL);
+        self.lua.set_hook(triggers, move |lua, _| {
+            let executed = executed.fetch_add(LIMIT_CHECK_INTERVAL as u64, Ordering::Relaxed)
+                + LIMIT_CHECK_INTERVAL as u64;
+            monitor.peak_memory_bytes.fetch_max(lua.used_memory(), Ordering::Relaxed);
+
+            // A frame at level max_call_depth means the call stack is deeper than allowed
+            if lua.inspect_stack(max_call_depth).is_some() {
+                *monitor.resource_limit.lock().unwrap() = Some(ResourceLimitKind::CallDepth);
+                return Err(fail_every_instruction(lua, format!(
+                    "call depth limit of {} exceeded", max_call_depth
+                )));
+            }
+
+            let status = if cancel_flag.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+                Some(ExecutionStatus::Cancelled)
+            } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                Some(ExecutionStatus::TimedOut)
+            } else if instruction_limit.is_some_and(|limit| executed > limit) {
+                Some(ExecutionStatus::InstructionLimitExceeded)
+            } else {
+                None
+            };
+
+            match status {
+                Some(status) => {
+                    *monitor.interrupted.lock().unwrap() = Some(status);
+                    Err(fail_every_instruction(lua, format!("script interrupted ({:?})", status)))
+                }
+                None => Ok(()),
+            }
+        });
+    }
+
+    #[allow(dead_code)]
     pub fn execute_script(&self, script: &str) -> LuaExecutionResult {
+        self.execute_script_with_limits(script, &ExecutionLimits::default())
+    }
+
+    pub fn execute_script_with_limits(&self, script: &str, limits: &ExecutionLimits) -> LuaExecutionResult {
         let start_time = std::time::Instant::now();
+        let monitor = Arc::new(RunMonitor::default());
+        monitor.peak_memory_bytes.store(self.lua.used_memory(), Ordering::Relaxed);
+        self.install_limit_hook(limits, Arc::clone(&monitor));
 
         // Clear output buffer and draw commands
         self.output_buffer.lock().unwrap().clear();
         self.draw_commands.lock().unwrap().clear();
+        self.batch_stream.lock().unwrap().reset();
+        *self.open_output_line.lock().unwrap() = None;
+        let streamed = self.batch_stream.lock().unwrap().sink.is_some();
 
-        println!("Executing Lua script (length: {})", script.len());
-        println!("Script content preview: {}", &script[..std::cmp::min(200, script.len())]);
+        eprintln!("Executing Lua script (length: {})", script.len());
+        eprintln!("Script content preview: {}", &script[..std::cmp::min(200, script.len())]);
 
         // Initialize ADekoLib before script execution
-        println!("Skipping ADekoLib.start() call to debug the issue");
+        eprintln!("Skipping ADekoLib.start() call to debug the issue");
         // match self.lua.load("ADekoLib.start()").exec() {
-        //     Ok(_) => println!("✓ ADekoLib.start() called successfully"),
-        //     Err(e) => println!("⚠ Warning: ADekoLib.start() failed: {}", e),
+        //     Ok(_) => eprintln!("✓ ADekoLib.start() called successfully"),
+        //     Err(e) => eprintln!("⚠ Warning: ADekoLib.start() failed: {}", e),
         // }
 
         // Execute the script
-        match self.lua.load(script).exec() {
+        let exec_result = self.lua.load(script).set_name(SCRIPT_CHUNK_NAME).exec();
+        self.lua.remove_hook();
+
+        // A script that caught the limit error with pcall and then returned was still stopped
+        let exec_result = exec_result.and_then(|_| {
+            let interrupted = *monitor.interrupted.lock().unwrap();
+            let resource_limit = *monitor.resource_limit.lock().unwrap();
+            match (interrupted, resource_limit) {
+                (Some(status), _) => Err(mlua::Error::RuntimeError(format!("script interrupted ({:?})", status))),
+                (None, Some(kind)) => Err(mlua::Error::RuntimeError(format!("resource limit exceeded ({:?})", kind))),
+                (None, None) => Ok(()),
+            }
+        });
+
+        match exec_result {
             Ok(_) => {
-                let mut output = self.output_buffer.lock().unwrap().join("\n");
-                let draw_commands = self.draw_commands.lock().unwrap().clone();
-                println!("Script execution completed successfully. Output lines: {}, Draw commands: {}",
-                    self.output_buffer.lock().unwrap().len(), draw_commands.len());
+                let draw_command_count = self.draw_commands.lock().unwrap().len();
+                eprintln!("Script execution completed successfully. Output lines: {}, Draw commands: {}",
+                    self.output_buffer.lock().unwrap().len(), draw_command_count);
 
                 // Debug: Check if modelMain function exists in global scope
                 let model_main_status = match self.lua.globals().get::<_, mlua::Function>("modelMain") {
                     Ok(_) => {
-                        println!("modelMain function found in global scope");
+                        eprintln!("modelMain function found in global scope");
                         "modelMain function found in global scope"
                     },
                     Err(_) => {
-                        println!("modelMain function NOT found in global scope");
+                        eprintln!("modelMain function NOT found in global scope");
                         "modelMain function NOT found in global scope"
                     }
                 };
 
                 // Add debug info to output so it's visible in the frontend
-                if !output.is_empty() {
-                    output.push_str("\n");
+                {
+                    let mut output = self.output_buffer.lock().unwrap();
+                    output.push(format!("DEBUG: {}", model_main_status));
+                    output.push(format!("DEBUG: Script execution time: {}ms", start_time.elapsed().as_millis()));
+                    output.push(format!("DEBUG: Draw commands generated: {}", draw_command_count));
                 }
-                output.push_str(&format!("DEBUG: {}", model_main_status));
-                output.push_str(&format!("\nDEBUG: Script execution time: {}ms", start_time.elapsed().as_millis()));
-                output.push_str(&format!("\nDEBUG: Draw commands generated: {}", draw_commands.len()));
 
                 // Debug: Print each draw command
-                for (i, cmd) in draw_commands.iter().enumerate() {
-                    println!("Draw command {}: {:?}", i, cmd);
+                for (i, cmd) in self.draw_commands.lock().unwrap().iter().enumerate() {
+                    eprintln!("Draw command {}: {:?}", i, cmd);
                 }
 
+                let (output, draw_commands, output_line_count) = self.finish_output(streamed);
                 LuaExecutionResult {
                     success: true,
+                    status: ExecutionStatus::Completed,
                     output,
                     error: String::new(),
                     execution_time_ms: start_time.elapsed().as_millis() as u64,
                     draw_commands,
+                    resource_limit: None,
+                    error_details: None,
+                    stopped_at: None,
+                    part_model: self.read_part_model(),
+                    streamed,
+                    output_line_count,
+                    draw_command_count,
                 }
             }
             Err(err) => {
-                println!("Script execution failed: {}", err);
+                eprintln!("Script execution failed: {}", err);
+                let source_map = self.source_map.lock().unwrap();
+                let error_message = match source_map.as_ref() {
+                    Some(source_map) => source_map.remap_message(&err.to_string()),
+                    None => err.to_string(),
+                };
+                let error_details = LuaErrorInfo::from_error(&err, source_map.as_ref());
+                let resource_limit = monitor.resource_limit.lock().unwrap()
+                    .or_else(|| resource_limit_kind(&err))
+                    .map(|kind| self.resource_limit_error(kind, &monitor));
+                let status = if resource_limit.is_some() {
+                    ExecutionStatus::ResourceLimitExceeded
+                } else {
+                    monitor.interrupted.lock().unwrap().unwrap_or(ExecutionStatus::Failed)
+                };
+                let error = match status {
+                    ExecutionStatus::TimedOut => format!(
+                        "Script execution timed out after {}ms",
+                        limits.timeout.unwrap_or_default().as_millis()
+                    ),
+                    ExecutionStatus::InstructionLimitExceeded => format!(
+                        "Script exceeded the limit of {} instructions",
+                        limits.instruction_limit.unwrap_or_default()
+                    ),
+                    ExecutionStatus::Cancelled => "Script execution was cancelled".to_string(),
+                    ExecutionStatus::ResourceLimitExceeded => resource_limit.as_ref()
+                        .map(|limit| limit.message.clone())
+                        .unwrap_or_default(),
+                    _ => format!("Lua execution error: {}", error_message),
+                };
+
+
+                // Keep everything produced before the failure and mark where it stopped
+                let draw_command_count = self.draw_commands.lock().unwrap().len();
+                let stopped_at = {
+                    let mut output = self.output_buffer.lock().unwrap();
+                    let marker = ExecutionStopMarker {
+                        output_line_index: output.len(),
+                        draw_command_index: draw_command_count,
+                        location: error_details.location.clone(),
+                        frame: error_details.frames.iter().find(|frame| frame.line.is_some()).cloned(),
+                    };
+                    let position = match (&marker.location, &marker.frame) {
+                        (Some(location), _) => format!(" at {}:{}", location.file, location.line),
+                        (None, Some(frame)) => format!(" at {}:{}", frame.chunk, frame.line.unwrap_or_default()),
+                        (None, None) => String::new(),
+                    };
+                    output.push(format!("--- Execution stopped{} ---", position));
+                    marker
+                };
+
+                let (output, draw_commands, output_line_count) = self.finish_output(streamed);
                 LuaExecutionResult {
                     success: false,
-                    output: String::new(),
-                    error: format!("Lua execution error: {}", err),
+                    status,
+                    output,
+                    error,
                     execution_time_ms: start_time.elapsed().as_millis() as u64,
-                    draw_commands: Vec::new(),
+                    draw_commands,
+                    resource_limit,
+                    error_details: Some(error_details),
+                    stopped_at: Some(stopped_at),
+                    part_model: self.read_part_model(),
+                    streamed,
+                    output_line_count,
+                    draw_command_count,
                 }
             }
         }
     }
+
+    // Sends the last pending batch; a streamed run returns only counts, not the collected output
+    fn finish_output(&self, streamed: bool) -> (String, Vec<DrawCommand>, usize) {
+        let output = self.output_buffer.lock().unwrap();
+        let draw_commands = self.draw_commands.lock().unwrap();
+        self.batch_stream.lock().unwrap().flush(&output, &draw_commands, true);
+
+        if streamed {
+            (String::new(), Vec::new(), output.len())
+        } else {
+            (output.join("\n"), draw_commands.clone(), output.len())
+        }
+    }
+
+    fn read_part_model(&self) -> Option<PartModel> {
+        match PartModel::from_globals(&self.lua.globals()) {
+            Ok(part_model) => part_model,
+            Err(e) => {
+                eprintln!("Failed to read the ADekoLib part model: {}", e);
+                None
+            }
+        }
+    }
+
+    fn resource_limit_error(&self, kind: ResourceLimitKind, monitor: &RunMonitor) -> ResourceLimitError {
+        let peak_memory_bytes = monitor.peak_memory_bytes.load(Ordering::Relaxed)
+            .max(self.lua.used_memory());
+        let limits = self.resource_limits;
+        let message = match kind {
+            ResourceLimitKind::Memory => format!(
+                "Resource limit exceeded: memory limit of {} bytes reached (peak usage {} bytes)",
+                limits.memory_limit_bytes, peak_memory_bytes
+            ),
+            ResourceLimitKind::CallDepth => format!(
+                "Resource limit exceeded: call depth limit of {} reached (peak memory usage {} bytes)",
+                limits.max_call_depth, peak_memory_bytes
+            ),
+        };
+
+        ResourceLimitError {
+            kind,
+            message,
+            peak_memory_bytes,
+            memory_limit_bytes: limits.memory_limit_bytes,
+            max_call_depth: limits.max_call_depth,
+        }
+    }
+}
+
+// A pcall in the script or the library catches the error a limit raises. From then on every
+// instruction fails, so the run stops at the first instruction outside the pcall.
+fn fail_every_instruction(lua: &Lua, message: String) -> mlua::Error {
+    let error = mlua::Error::RuntimeError(message);
+    let raised = error.clone();
+    lua.set_hook(HookTriggers::new().every_nth_instruction(1), move |_, _| Err(raised.clone()));
+    error
+}
+
+// Appends text the way io.write does: a line stays open until a newline is written.
+// Any other push to the output leaves the open index stale, which closes the line.
+fn write_output(output: &mut Vec<String>, open_line: &mut Option<usize>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    let mut parts = text.split('\n');
+    let first = parts.next().unwrap_or_default();
+    match *open_line {
+        Some(index) if index + 1 == output.len() => output[index].push_str(first),
+        _ => output.push(first.to_string()),
+    }
+    for part in parts {
+        output.push(part.to_string());
+    }
+
+    // A trailing newline closes the last line instead of opening an empty one
+    if text.ends_with('\n') {
+        output.pop();
+        *open_line = None;
+    } else {
+        *open_line = Some(output.len() - 1);
+    }
+}
+
+// Classify errors raised by the VM itself when it runs out of memory or stack
+fn resource_limit_kind(err: &mlua::Error) -> Option<ResourceLimitKind> {
+    match err {
+        mlua::Error::MemoryError(_) => Some(ResourceLimitKind::Memory),
+        mlua::Error::StackError => Some(ResourceLimitKind::CallDepth),
+        mlua::Error::RuntimeError(message) if message.contains("not enough memory") => {
+            Some(ResourceLimitKind::Memory)
+        }
+        mlua::Error::RuntimeError(message) if message.contains("stack overflow") => {
+            Some(ResourceLimitKind::CallDepth)
+        }
+        mlua::Error::CallbackError { cause, .. } => resource_limit_kind(cause),
+        mlua::Error::WithContext { cause, .. } => resource_limit_kind(cause),
+        _ => None,
+    }
 }
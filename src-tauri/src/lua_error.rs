@@ -0,0 +1,115 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::source_map::{SourceLocation, SourceMap, SCRIPT_SHORT_NAME};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LuaErrorKind {
+    Syntax,
+    Runtime,
+    Memory,
+    Callback,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackFrame {
+    pub function: Option<String>, // None for anonymous functions and C code
+    pub chunk: String,
+    pub line: Option<usize>,
+}
+
+/// Typed view of an `mlua::Error` chain together with its traceback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LuaErrorInfo {
+    pub kind: LuaErrorKind,
+    pub message: String,
+    pub location: Option<SourceLocation>,
+    pub frames: Vec<StackFrame>,
+}
+
+impl LuaErrorInfo {
+    pub fn from_error(err: &mlua::Error, source_map: Option<&SourceMap>) -> Self {
+        let (kind, message, traceback) = describe(err);
+        let location = source_map.and_then(|map| map.first_location(&message));
+        let message = match source_map {
+            Some(map) => map.remap_message(&message),
+            None => message,
+        };
+
+        Self {
+            kind,
+            message,
+            location,
+            frames: parse_traceback(&traceback, source_map),
+        }
+    }
+}
+
+// Walks the error chain down to the error raised by Lua, keeping the kind of the outermost layer
+fn describe(err: &mlua::Error) -> (LuaErrorKind, String, String) {
+    match err {
+        mlua::Error::SyntaxError { message, .. } => (LuaErrorKind::Syntax, message.clone(), String::new()),
+        mlua::Error::MemoryError(message) => (LuaErrorKind::Memory, message.clone(), String::new()),
+        mlua::Error::RuntimeError(message) => {
+            let (message, traceback) = split_traceback(message);
+            (LuaErrorKind::Runtime, message, traceback)
+        }
+        mlua::Error::CallbackError { traceback, cause } => {
+            let (_, message, inner_traceback) = describe(cause);
+            let traceback = if inner_traceback.is_empty() { traceback.clone() } else { inner_traceback };
+            (LuaErrorKind::Callback, message, traceback)
+        }
+        mlua::Error::WithContext { context, cause } => {
+            let (kind, message, traceback) = describe(cause);
+            (kind, format!("{}: {}", context, message), traceback)
+        }
+        other => (LuaErrorKind::Runtime, other.to_string(), String::new()),
+    }
+}
+
+fn split_traceback(message: &str) -> (String, String) {
+    match message.find("stack traceback:") {
+        Some(index) => (message[..index].trim_end().to_string(), message[index..].to_string()),
+        None => (message.to_string(), String::new()),
+    }
+}
+
+// Parses the standard luaL_traceback format: "\t<chunk>:<line>: in <what>"
+fn parse_traceback(traceback: &str, source_map: Option<&SourceMap>) -> Vec<StackFrame> {
+    let frame_regex = Regex::new(r"^(.+?):(?:(\d+):)? in (.+)$").unwrap();
+
+    traceback
+        .lines()
+        .skip_while(|line| !line.starts_with("stack traceback:"))
+        .skip(1)
+        .filter_map(|line| frame_regex.captures(line.trim()))
+        .map(|captures| {
+            let mut chunk = captures[1].to_string();
+            let mut line = captures.get(2).and_then(|m| m.as_str().parse::<usize>().ok());
+
+            if chunk == SCRIPT_SHORT_NAME {
+                if let Some(location) = line.and_then(|l| source_map.and_then(|map| map.resolve(l))) {
+                    chunk = location.file;
+                    line = Some(location.line);
+                }
+            }
+
+            StackFrame {
+                function: function_name(&captures[3]),
+                chunk,
+                line,
+            }
+        })
+        .collect()
+}
+
+fn function_name(what: &str) -> Option<String> {
+    if what == "main chunk" {
+        return Some(what.to_string());
+    }
+
+    // "function 'ADekoLib.offSet'", "local 'f'", "method 'draw'", "upvalue 'helper'", ...
+    let start = what.find('\'')?;
+    let end = what.rfind('\'')?;
+    (end > start).then(|| what[start + 1..end].to_string())
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use crate::layer_tool::{LayerToolSpec, ToolShape};
+use crate::part_model::{ModelShape, PanelFace};
+use crate::tool_library::{CncTool, ToolLibrary};
+
+// Keeps a tiny cell size on a large panel from allocating gigabytes
+const MAX_CELLS: usize = 20_000_000;
+const CELL_SIZE_RANGE: (f64, f64) = (0.05, 10.0);
+
+// Layer ADekoLib.makePart draws the panel outline on, it is not a cut
+pub const PANEL_LAYER: &str = "PANEL";
+
+// Sizes used when neither the library nor the layer name gives one, like the frontend's virtual tools
+const FALLBACK_DIAMETER: f64 = 6.0;
+const FALLBACK_V_ANGLE: f64 = 90.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationOptions {
+    pub cell_size: f64, // mm per heightmap cell
+    pub face: PanelFace,
+    pub material_thickness: f64,
+    pub panel_width: Option<f64>, // Taken from the PANEL shape when not given
+    pub panel_height: Option<f64>,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self { cell_size: 1.0, face: PanelFace::Top, material_thickness: 18.0, panel_width: None, panel_height: None }
+    }
+}
+
+/// Material surface of the panel face in a regular grid. Heights are relative to the untouched
+/// surface: 0 is not machined, -material_thickness is cut through. Rows run along +Y from `origin`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heightmap {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f64,
+    pub origin: [f64; 2],
+    pub heights: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerRemoval {
+    pub layer_name: String,
+    pub tool_id: Option<String>, // `None` when the tool was derived from the layer name only
+    pub tool_shape: ToolShape,
+    pub tool_diameter: f64,
+    pub shape_count: usize,
+    pub removed_volume: f64, // mm³, material already removed by an earlier layer is not counted again
+    pub max_depth: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationResult {
+    pub heightmap: Heightmap,
+    pub layers: Vec<LayerRemoval>,
+    pub removed_volume: f64,
+}
+
+/// Cross-section of a tool: height of the cutting edge above the tip at a distance from the axis.
+#[derive(Debug, Clone, Copy)]
+enum ToolProfile {
+    Flat { radius: f64 },
+    Cone { radius: f64, tip_radius: f64, half_angle: f64 },
+    Ball { radius: f64, ball_radius: f64 },
+    Corner { radius: f64, corner_radius: f64 },
+}
+
+impl ToolProfile {
+    fn from_tool(tool: &CncTool) -> Self {
+        let radius = tool.diameter / 2.0;
+        match tool.shape {
+            ToolShape::Conical => ToolProfile::Cone {
+                radius,
+                tip_radius: tool.tip_diameter.unwrap_or_default() / 2.0,
+                half_angle: tool.tip_angle.unwrap_or(FALLBACK_V_ANGLE).to_radians() / 2.0,
+            },
+            ToolShape::Ballnose => ToolProfile::Ball { radius, ball_radius: tool.ball_radius.unwrap_or(radius) },
+            ToolShape::Radial => ToolProfile::Corner { radius, corner_radius: tool.corner_radius.unwrap_or_default() },
+            ToolShape::Cylindrical | ToolShape::Special => ToolProfile::Flat { radius },
+        }
+    }
+
+    fn from_spec(spec: &LayerToolSpec) -> Self {
+        let radius = spec.diameter.unwrap_or(FALLBACK_DIAMETER) / 2.0;
+        match spec.tool_shape {
+            Some(ToolShape::Conical) => {
+                // Same width the frontend gives a virtual V-bit
+                let angle = spec.angle.unwrap_or(FALLBACK_V_ANGLE);
+                ToolProfile::Cone { radius: (angle / 5.0).max(10.0) / 2.0, tip_radius: 0.0, half_angle: angle.to_radians() / 2.0 }
+            }
+            Some(ToolShape::Ballnose) => ToolProfile::Ball { radius, ball_radius: radius },
+            _ => ToolProfile::Flat { radius },
+        }
+    }
+
+    fn radius(self) -> f64 {
+        match self {
+            ToolProfile::Flat { radius }
+            | ToolProfile::Cone { radius, .. }
+            | ToolProfile::Ball { radius, .. }
+            | ToolProfile::Corner { radius, .. } => radius,
+        }
+    }
+
+    fn height_at(self, distance: f64) -> f64 {
+        match self {
+            ToolProfile::Flat { .. } => 0.0,
+            ToolProfile::Cone { tip_radius, half_angle, .. } => (distance - tip_radius).max(0.0) / half_angle.tan(),
+            ToolProfile::Ball { ball_radius, .. } => {
+                let r = distance.min(ball_radius);
+                ball_radius - (ball_radius * ball_radius - r * r).sqrt()
+            }
+            ToolProfile::Corner { radius, corner_radius } => {
+                let r = distance - (radius - corner_radius);
+                if r <= 0.0 { 0.0 } else { corner_radius - (corner_radius * corner_radius - r * r).max(0.0).sqrt() }
+            }
+        }
+    }
+}
+
+/// Sweeps the tool of every machined shape on `options.face` along its path and records the lowest
+/// point the tool reaches in each cell. Tools come from the library, falling back to the layer name.
+pub fn simulate_machining(shapes: &[ModelShape], library: &ToolLibrary, options: &SimulationOptions) -> Result<SimulationResult, String> {
+    if !(CELL_SIZE_RANGE.0..=CELL_SIZE_RANGE.1).contains(&options.cell_size) {
+        return Err(format!(
+            "Cell size must be between {} and {} mm, got {}",
+            CELL_SIZE_RANGE.0, CELL_SIZE_RANGE.1, options.cell_size
+        ));
+    }
+    if !(options.material_thickness > 0.0 && options.material_thickness.is_finite()) {
+        return Err(format!("Invalid material thickness: {}", options.material_thickness));
+    }
+
+    let panel_bounds = shapes
+        .iter()
+        .filter(|shape| shape.layer_name == PANEL_LAYER && shape.face == Some(options.face))
+        .find_map(|shape| shape.bounds());
+    let (origin, size) = match (options.panel_width, options.panel_height, panel_bounds) {
+        (Some(width), Some(height), _) => ([0.0, 0.0], [width, height]),
+        (width, height, Some(bounds)) => (
+            [bounds.min.x, bounds.min.y],
+            [width.unwrap_or(bounds.max.x - bounds.min.x), height.unwrap_or(bounds.max.y - bounds.min.y)],
+        ),
+        _ => return Err("The panel size is not known, the model has no PANEL shape on this face".to_string()),
+    };
+    if !(size[0] > 0.0 && size[1] > 0.0) {
+        return Err(format!("Invalid panel size {} x {}", size[0], size[1]));
+    }
+
+    let columns = (size[0] / options.cell_size).ceil() as usize;
+    let rows = (size[1] / options.cell_size).ceil() as usize;
+    if columns.saturating_mul(rows) > MAX_CELLS {
+        return Err(format!(
+            "A {} x {} heightmap is over the limit of {} cells, use a larger cell size",
+            columns, rows, MAX_CELLS
+        ));
+    }
+
+    let mut heightmap = Heightmap { columns, rows, cell_size: options.cell_size, origin, heights: vec![0.0; columns * rows] };
+    let mut layers: Vec<LayerRemoval> = Vec::new();
+
+    for shape in shapes {
+        if shape.face != Some(options.face)
+            || shape.layer_name == PANEL_LAYER
+            || !shape.tool_spec.is_machined()
+            || shape.nodes.is_empty()
+        {
+            continue;
+        }
+
+        let tool = library.resolve_for_layer(&shape.layer_name);
+        let profile = tool.map(ToolProfile::from_tool).unwrap_or_else(|| ToolProfile::from_spec(&shape.tool_spec));
+        let (removed, max_depth) = sweep_shape(&mut heightmap, shape, profile, options.material_thickness);
+
+        let index = match layers.iter().position(|layer| layer.layer_name == shape.layer_name) {
+            Some(index) => index,
+            None => {
+                layers.push(LayerRemoval {
+                    layer_name: shape.layer_name.clone(),
+                    tool_id: tool.map(|tool| tool.id.clone()),
+                    tool_shape: tool.map(|tool| tool.shape).or(shape.tool_spec.tool_shape).unwrap_or(ToolShape::Cylindrical),
+                    tool_diameter: profile.radius() * 2.0,
+                    shape_count: 0,
+                    removed_volume: 0.0,
+                    max_depth: 0.0,
+                });
+                layers.len() - 1
+            }
+        };
+        let layer = &mut layers[index];
+        layer.shape_count += 1;
+        layer.removed_volume += removed;
+        layer.max_depth = layer.max_depth.max(max_depth);
+    }
+
+    Ok(SimulationResult {
+        removed_volume: layers.iter().map(|layer| layer.removed_volume).sum(),
+        heightmap,
+        layers,
+    })
+}
+
+// Returns the removed volume and the deepest tip position
+fn sweep_shape(heightmap: &mut Heightmap, shape: &ModelShape, profile: ToolProfile, material_thickness: f64) -> (f64, f64) {
+    let step = heightmap.cell_size / 2.0;
+    let cell_area = heightmap.cell_size * heightmap.cell_size;
+    let has_depth_profile = shape.has_depth_profile();
+
+    let mut removed = 0.0;
+    let mut max_depth: f64 = 0.0;
+    for [x, y, z] in shape.path_points(step) {
+        let z = if has_depth_profile { z } else { -shape.depth() };
+        max_depth = max_depth.max(-z);
+        removed += stamp(heightmap, x, y, z, profile, material_thickness) * cell_area;
+    }
+    (removed, max_depth)
+}
+
+// Lowers the cells under the tool, returns the removed height summed over the cells
+fn stamp(heightmap: &mut Heightmap, x: f64, y: f64, tip_z: f64, profile: ToolProfile, material_thickness: f64) -> f64 {
+    let radius = profile.radius();
+    let cell_size = heightmap.cell_size;
+    let [origin_x, origin_y] = heightmap.origin;
+    if x + radius < origin_x
+        || y + radius < origin_y
+        || x - radius > origin_x + heightmap.columns as f64 * cell_size
+        || y - radius > origin_y + heightmap.rows as f64 * cell_size
+    {
+        return 0.0;
+    }
+
+    let to_cell = |value: f64, origin: f64, count: usize| ((value - origin) / cell_size).floor().clamp(0.0, count as f64 - 1.0) as usize;
+
+    let (min_column, max_column) = (to_cell(x - radius, origin_x, heightmap.columns), to_cell(x + radius, origin_x, heightmap.columns));
+    let (min_row, max_row) = (to_cell(y - radius, origin_y, heightmap.rows), to_cell(y + radius, origin_y, heightmap.rows));
+
+    let mut removed = 0.0;
+    for row in min_row..=max_row {
+        let cell_y = origin_y + (row as f64 + 0.5) * cell_size;
+        for column in min_column..=max_column {
+            let cell_x = origin_x + (column as f64 + 0.5) * cell_size;
+            let distance = ((cell_x - x).powi(2) + (cell_y - y).powi(2)).sqrt();
+            if distance > radius {
+                continue;
+            }
+
+            let bottom = (tip_z + profile.height_at(distance)).max(-material_thickness);
+            let cell = &mut heightmap.heights[row * heightmap.columns + column];
+            if bottom < *cell as f64 {
+                removed += *cell as f64 - bottom;
+                *cell = bottom as f32;
+            }
+        }
+    }
+    removed
+}
@@ -1,14 +1,50 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use configparser::ini::Ini;
 use encoding_rs::{UTF_8, WINDOWS_1252, WINDOWS_1254, ISO_8859_2};
-
-mod lua_engine;
-use lua_engine::{NativeLuaEngine, LuaExecutionResult};
+use tauri::Emitter;
+
+use lua_macro_editor::{
+    dxf_export, gcode_export, geometry, layer_tool, lua_engine, machining_simulation, model_check, model_script,
+    model_sweep, panel_mesh, part_model, svg_export, tool_library,
+};
+use gcode_export::{PostProcessorConfig, ToolMapping};
+use geometry::{Bounds, Point};
+use layer_tool::LayerToolSpec;
+use lua_engine::{DrawCommand, ExecutionBatch, ExecutionLimits, ModelParameters, NativeLuaEngine, LuaExecutionResult, ResourceLimits};
+use machining_simulation::{SimulationOptions, SimulationResult};
+use model_check::ModelCheckReport;
+use model_script::{build_model_script, ScriptMode};
+use model_sweep::{ModelSweep, SweepReport};
+use panel_mesh::MeshSummary;
+use tool_library::{CncTool, ToolLibrary};
+use part_model::{PanelFace, PartModel};
+
+// Default per-run limits so an endless loop cannot lock the Lua VM forever
+const DEFAULT_EXECUTION_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_INSTRUCTION_LIMIT: u64 = 2_000_000_000;
+
+/// Cancel flags of the Lua runs currently in progress, keyed by run id
+#[derive(Default)]
+struct LuaRunRegistry(Mutex<HashMap<String, Arc<AtomicBool>>>);
+
+// Event carrying the incremental print output and draw commands of a streamed run
+const LUA_EXECUTION_BATCH_EVENT: &str = "lua-execution-batch";
+
+#[derive(Clone, Serialize)]
+struct LuaExecutionBatchEvent {
+    run_id: Option<String>,
+    #[serde(flatten)]
+    batch: ExecutionBatch,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AppSettings {
@@ -55,6 +91,10 @@ fn get_settings_file_path() -> Result<PathBuf, String> {
     Ok(app_config_dir.join("settings.ini"))
 }
 
+fn get_tool_library_file_path() -> Result<PathBuf, String> {
+    Ok(get_settings_file_path()?.with_file_name("tools.json"))
+}
+
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 #[tauri::command]
 fn read_file(path: String) -> Result<String, String> {
@@ -425,6 +465,79 @@ fn save_settings(settings: AppSettings) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+fn get_cnc_tools() -> Result<Vec<CncTool>, String> {
+    Ok(ToolLibrary::load(&get_tool_library_file_path()?)?.tools)
+}
+
+#[tauri::command]
+fn add_cnc_tool(tool: CncTool) -> Result<Vec<CncTool>, String> {
+    let library_path = get_tool_library_file_path()?;
+    let mut library = ToolLibrary::load(&library_path)?;
+    library.add(tool)?;
+    library.save(&library_path)?;
+    Ok(library.tools)
+}
+
+#[tauri::command]
+fn update_cnc_tool(tool: CncTool) -> Result<Vec<CncTool>, String> {
+    let library_path = get_tool_library_file_path()?;
+    let mut library = ToolLibrary::load(&library_path)?;
+    library.update(tool)?;
+    library.save(&library_path)?;
+    Ok(library.tools)
+}
+
+#[tauri::command]
+fn delete_cnc_tool(id: String) -> Result<Vec<CncTool>, String> {
+    let library_path = get_tool_library_file_path()?;
+    let mut library = ToolLibrary::load(&library_path)?;
+    if !library.remove(&id) {
+        return Err(format!("Tool '{}' does not exist", id));
+    }
+    library.save(&library_path)?;
+    Ok(library.tools)
+}
+
+#[tauri::command]
+fn import_cnc_tools(path: String, replace: Option<bool>) -> Result<Vec<CncTool>, String> {
+    let resolved_path = if path.starts_with("./") {
+        let project_root = get_project_root()?;
+        project_root.join(&path[2..])
+    } else {
+        PathBuf::from(&path)
+    };
+
+    let library_path = get_tool_library_file_path()?;
+    let mut library = ToolLibrary::load(&library_path)?;
+    library.import(&resolved_path, replace.unwrap_or(false))?;
+    library.save(&library_path)?;
+    Ok(library.tools)
+}
+
+#[tauri::command]
+fn export_cnc_tools(path: String) -> Result<(), String> {
+    let resolved_path = if path.starts_with("./") {
+        let project_root = get_project_root()?;
+        project_root.join(&path[2..])
+    } else {
+        PathBuf::from(&path)
+    };
+
+    ToolLibrary::load(&get_tool_library_file_path()?)?.save(&resolved_path)
+}
+
+#[tauri::command]
+fn resolve_cnc_tool(id: Option<String>, layer_name: Option<String>) -> Result<Option<CncTool>, String> {
+    let library = ToolLibrary::load(&get_tool_library_file_path()?)?;
+    let tool = match (id, layer_name) {
+        (Some(id), _) => library.get(&id),
+        (None, Some(layer_name)) => library.resolve_for_layer(&layer_name),
+        (None, None) => return Err("Either a tool id or a layer name is needed".to_string()),
+    };
+    Ok(tool.cloned())
+}
+
 #[tauri::command]
 fn get_lua_library_path(model_library_path: String) -> Result<String, String> {
     let model_path = Path::new(&model_library_path);
@@ -451,120 +564,331 @@ async fn execute_lua_script(
     script_content: String,
     lua_library_path: String,
     debug_mode: bool,
+    product_mode: Option<bool>,
+    script_name: Option<String>,
+    run_id: Option<String>,
+    timeout_ms: Option<u64>,
+    instruction_limit: Option<u64>,
+    memory_limit_mb: Option<usize>,
+    max_call_depth: Option<usize>,
+    stream_events: Option<bool>,
+    model_parameters: Option<ModelParameters>,
+    app: tauri::AppHandle,
+    runs: tauri::State<'_, LuaRunRegistry>,
 ) -> Result<LuaExecutionResult, String> {
-    // Create a new native Lua engine instance with library path
-    let engine = NativeLuaEngine::new_with_library_path(Some(lua_library_path.clone()))
-        .map_err(|e| format!("Failed to create Lua engine: {}", e))?;
-
-    // Prepare the script content with library includes if needed
-    let mut full_script = String::new();
-
-    // AdekoLib.lua is now bundled with the app and loaded automatically by the Lua engine
-    println!("=== USING BUNDLED ADEKO LIB ===");
-    println!("AdekoLib.lua is bundled with the app and loaded automatically");
-    println!("No need to load from external files");
-
-    // Skip loading external turtle.lua - we use built-in turtle functions
-    println!("=== USING BUILT-IN TURTLE FUNCTIONS ===");
-    println!("Skipping external turtle.lua (uses wxWidgets which is not available)");
-    println!("Using built-in turtle graphics implementation instead");
-
-    // Open turtle graphics window using built-in function
-    full_script.push_str("-- Using built-in turtle graphics\n");
-    full_script.push_str("-- open('Lua Debug - Turtle Graphics')\n");
-
-    if debug_mode {
-        // In debug mode, we emulate ZeroBrane's behavior:
-        // 1. First load the user script to define modelMain()
-        // 2. Then execute ADekoDebugMode.lua which sets up 6-face layout and calls modelMain()
-        println!("🔧 Debug mode: Emulating ZeroBrane-style debugging");
-
-        // Add the user script which defines modelMain()
-        full_script.push_str("\n-- ===== USER SCRIPT (defines modelMain function) =====\n");
-        full_script.push_str(&script_content);
-        full_script.push_str("\n\n");
-
-        // Load ADekoDebugMode.lua from file (will be packaged with the app)
-        println!("📋 Debug mode: Loading ADekoDebugMode.lua for 6-face layout");
-        let debug_mode_path = Path::new(&lua_library_path).join("ADekoDebugMode.lua");
-        println!("📁 Attempting to load ADekoDebugMode.lua from: {}", debug_mode_path.display());
-
-        if debug_mode_path.exists() {
-            match fs::read_to_string(&debug_mode_path) {
-                Ok(debug_content) => {
-                    println!("✅ Successfully loaded ADekoDebugMode.lua ({} bytes)", debug_content.len());
-                    full_script.push_str("-- ===== ADEKOLIB DEBUG MODE (ZeroBrane-style 6-face layout) =====\n");
-                    full_script.push_str("print('🎯 Starting ZeroBrane-style debug mode with 6-face layout')\n");
-                    full_script.push_str(&debug_content);
-                    full_script.push_str("\nprint('✅ Debug mode execution completed')\n");
-                }
-                Err(e) => {
-                    println!("❌ Failed to read ADekoDebugMode.lua: {}", e);
-                    // Enhanced fallback with better ZeroBrane emulation
-                    full_script.push_str("-- ===== FALLBACK DEBUG ENVIRONMENT =====\n");
-                    full_script.push_str("print('⚠️  Using fallback debug environment (ADekoDebugMode.lua not found)')\n");
-                    full_script.push_str("X = 500\nY = 700\nmaterialThickness = 18\noffset = 20\n");
-                    full_script.push_str("edge1layer=\"LMM0\"\nedge2layer=\"LMM1\"\nedge3layer=\"LMM2\"\nedge4layer=\"LMM3\"\n");
-                    full_script.push_str("edge1thickness=0.1\nedge2thickness=0.2\nedge3thickness=0.3\nedge4thickness=0.4\n");
-                    full_script.push_str("doesSizeIncludeEdgeThickness=\"false\"\nmodelParameters=\"\"\n");
-                    full_script.push_str("print('🔧 Setting up basic debug environment')\n");
-                    full_script.push_str("if ADekoLib then ADekoLib.start() end\n");
-                    full_script.push_str("if modelMain then\n");
-                    full_script.push_str("  print('📞 Calling modelMain() function')\n");
-                    full_script.push_str("  modelMain()\n");
-                    full_script.push_str("  print('✅ modelMain() execution completed')\n");
-                    full_script.push_str("else\n");
-                    full_script.push_str("  print('❌ modelMain function not found in script')\n");
-                    full_script.push_str("end\n");
-                    full_script.push_str("if ADekoLib then ADekoLib.finish() end\n");
+    // Panel globals (X, Y, edge bands, ...) are set on the engine, so the script and the fallback blocks only read them
+    let product_mode = product_mode.unwrap_or(false);
+    let model_parameters = model_parameters.unwrap_or_else(|| {
+        if product_mode { ModelParameters::product_defaults() } else { ModelParameters::default() }
+    });
+    model_parameters.validate()?;
+
+    let script_name = script_name.unwrap_or_else(|| "user_script.lua".to_string());
+    let mode = ScriptMode::from_flags(debug_mode, product_mode);
+    let (full_script, source_map) = build_model_script(&script_content, &script_name, &lua_library_path, mode);
+
+    // Debug: Print the final script that will be executed
+    println!("=== FINAL SCRIPT TO EXECUTE ===");
+    println!("{}", full_script);
+    println!("=== END OF SCRIPT ===");
+
+    // Register the run so it can be cancelled while it executes
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    if let Some(ref id) = run_id {
+        runs.0.lock().unwrap().insert(id.clone(), Arc::clone(&cancel_flag));
+    }
+
+    let limits = ExecutionLimits {
+        timeout: Some(Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_EXECUTION_TIMEOUT_MS))),
+        instruction_limit: Some(instruction_limit.unwrap_or(DEFAULT_INSTRUCTION_LIMIT)),
+        cancel_flag: Some(cancel_flag),
+    };
+
+    let defaults = ResourceLimits::default();
+    let resource_limits = ResourceLimits {
+        memory_limit_bytes: memory_limit_mb
+            .map(|mb| mb * 1024 * 1024)
+            .unwrap_or(defaults.memory_limit_bytes),
+        max_call_depth: max_call_depth.unwrap_or(defaults.max_call_depth),
+    };
+
+    let event_run_id = run_id.clone();
+
+    // Execute the script using the native Lua engine on a blocking thread,
+    // so cancel_lua_execution can still be served while it runs
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<LuaExecutionResult, String> {
+        let engine = NativeLuaEngine::new_with_resource_limits(Some(lua_library_path), resource_limits)
+            .map_err(|e| format!("Failed to create Lua engine: {}", e))?;
+        engine.set_source_map(source_map);
+        engine.set_model_parameters(&model_parameters)
+            .map_err(|e| format!("Failed to set model parameters: {}", e))?;
+
+        if stream_events.unwrap_or(false) {
+            engine.set_batch_sink(Arc::new(move |batch| {
+                let event = LuaExecutionBatchEvent {
+                    run_id: event_run_id.clone(),
+                    batch,
+                };
+                if let Err(e) = app.emit(LUA_EXECUTION_BATCH_EVENT, event) {
+                    println!("Failed to emit Lua execution batch: {}", e);
                 }
-            }
-        } else {
-            println!("❌ ADekoDebugMode.lua not found at: {}", debug_mode_path.display());
-            // Enhanced fallback with better ZeroBrane emulation
-            full_script.push_str("-- ===== SIMPLIFIED DEBUG ENVIRONMENT =====\n");
-            full_script.push_str("print('⚠️  ADekoDebugMode.lua not found, using simplified debug environment')\n");
-            full_script.push_str("X = 500\nY = 700\nmaterialThickness = 18\noffset = 20\n");
-            full_script.push_str("edge1layer=\"LMM0\"\nedge2layer=\"LMM1\"\nedge3layer=\"LMM2\"\nedge4layer=\"LMM3\"\n");
-            full_script.push_str("edge1thickness=0.1\nedge2thickness=0.2\nedge3thickness=0.3\nedge4thickness=0.4\n");
-            full_script.push_str("doesSizeIncludeEdgeThickness=\"false\"\nmodelParameters=\"\"\n");
-            full_script.push_str("print('🔧 Setting up basic debug environment')\n");
-            full_script.push_str("if ADekoLib then ADekoLib.start() end\n");
-            full_script.push_str("if modelMain then\n");
-            full_script.push_str("  print('📞 Calling modelMain() function')\n");
-            full_script.push_str("  modelMain()\n");
-            full_script.push_str("  print('✅ modelMain() execution completed')\n");
-            full_script.push_str("else\n");
-            full_script.push_str("  print('❌ modelMain function not found in script')\n");
-            full_script.push_str("end\n");
-            full_script.push_str("if ADekoLib then ADekoLib.finish() end\n");
+            }));
         }
 
-        println!("Debug mode: Final script length: {}", full_script.len());
+        Ok(engine.execute_script_with_limits(&full_script, &limits))
+    })
+    .await
+    .map_err(|e| format!("Lua execution task failed: {}", e))
+    .and_then(|result| result);
 
-        // Debug: Show the last few lines of the script to check for syntax issues
-        let lines: Vec<&str> = full_script.lines().collect();
-        let total_lines = lines.len();
-        println!("Debug mode: Total lines in final script: {}", total_lines);
-        if total_lines > 10 {
-            println!("Debug mode: Last 10 lines of script:");
-            for (i, line) in lines.iter().enumerate().skip(total_lines - 10) {
-                println!("  {}: {}", i + 1, line);
-            }
+    if let Some(ref id) = run_id {
+        runs.0.lock().unwrap().remove(id);
+    }
+
+    result
+}
+
+#[tauri::command]
+async fn sweep_model(
+    script_content: String,
+    lua_library_path: String,
+    debug_mode: bool,
+    product_mode: Option<bool>,
+    script_name: Option<String>,
+    sweep: ModelSweep,
+    run_id: Option<String>,
+    timeout_ms: Option<u64>,
+    instruction_limit: Option<u64>,
+    runs: tauri::State<'_, LuaRunRegistry>,
+) -> Result<SweepReport, String> {
+    let product_mode = product_mode.unwrap_or(false);
+    let script_name = script_name.unwrap_or_else(|| "user_script.lua".to_string());
+    let mode = ScriptMode::from_flags(debug_mode, product_mode);
+    let (full_script, source_map) = build_model_script(&script_content, &script_name, &lua_library_path, mode);
+
+    // The whole sweep shares one cancel flag, cancelling stops it after the current case
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    if let Some(ref id) = run_id {
+        runs.0.lock().unwrap().insert(id.clone(), Arc::clone(&cancel_flag));
+    }
+
+    // The limits apply to each case, not to the sweep as a whole
+    let limits = ExecutionLimits {
+        timeout: Some(Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_EXECUTION_TIMEOUT_MS))),
+        instruction_limit: Some(instruction_limit.unwrap_or(DEFAULT_INSTRUCTION_LIMIT)),
+        cancel_flag: Some(cancel_flag),
+    };
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        model_sweep::run_sweep(
+            &full_script,
+            &source_map,
+            Some(lua_library_path),
+            product_mode,
+            &sweep,
+            &limits,
+            ResourceLimits::default(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Model sweep task failed: {}", e))
+    .and_then(|result| result);
+
+    if let Some(ref id) = run_id {
+        runs.0.lock().unwrap().remove(id);
+    }
+
+    result
+}
+
+#[tauri::command]
+fn cancel_lua_execution(run_id: String, runs: tauri::State<'_, LuaRunRegistry>) -> Result<bool, String> {
+    // Returns false when no run with this id is in progress
+    match runs.0.lock().unwrap().get(&run_id) {
+        Some(cancel_flag) => {
+            cancel_flag.store(true, Ordering::Relaxed);
+            Ok(true)
         }
-    } else {
-        // Normal mode: just add the user script
-        full_script.push_str("\n-- User Script:\n");
-        full_script.push_str(&script_content);
+        None => Ok(false),
     }
+}
 
-    // Debug: Print the final script that will be executed
-    println!("=== FINAL SCRIPT TO EXECUTE ===");
-    println!("{}", full_script);
-    println!("=== END OF SCRIPT ===");
+#[tauri::command]
+fn export_dxf(
+    path: String,
+    part_model: PartModel,
+    part_index: Option<usize>,
+    face: Option<PanelFace>,
+) -> Result<(), String> {
+    let resolved_path = if path.starts_with("./") {
+        let project_root = get_project_root()?;
+        project_root.join(&path[2..])
+    } else {
+        PathBuf::from(&path)
+    };
+
+    let shapes = dxf_export::select_shapes(&part_model, part_index)?;
+    let dxf = dxf_export::build_dxf(shapes, face.unwrap_or(PanelFace::Top));
+    fs::write(&resolved_path, dxf)
+        .map_err(|e| format!("Failed to write DXF file: {}", e))
+}
+
+#[tauri::command]
+fn export_svg(path: String, draw_commands: Vec<DrawCommand>) -> Result<(), String> {
+    let resolved_path = if path.starts_with("./") {
+        let project_root = get_project_root()?;
+        project_root.join(&path[2..])
+    } else {
+        PathBuf::from(&path)
+    };
+
+    fs::write(&resolved_path, svg_export::build_svg(&draw_commands))
+        .map_err(|e| format!("Failed to write SVG file: {}", e))
+}
+
+#[tauri::command]
+fn export_gcode(
+    path: String,
+    part_model: PartModel,
+    part_index: Option<usize>,
+    config: Option<PostProcessorConfig>,
+    tool_mapping: Vec<ToolMapping>,
+) -> Result<(), String> {
+    let resolved_path = if path.starts_with("./") {
+        let project_root = get_project_root()?;
+        project_root.join(&path[2..])
+    } else {
+        PathBuf::from(&path)
+    };
 
-    // Execute the script using the native Lua engine
-    Ok(engine.execute_script(&full_script))
+    let shapes = dxf_export::select_shapes(&part_model, part_index)?;
+    let gcode = gcode_export::generate_gcode(shapes, &config.unwrap_or_default(), &tool_mapping)?;
+    fs::write(&resolved_path, gcode)
+        .map_err(|e| format!("Failed to write G-code file: {}", e))
+}
+
+#[tauri::command]
+fn simulate_machining(
+    part_model: PartModel,
+    part_index: Option<usize>,
+    options: Option<SimulationOptions>,
+) -> Result<SimulationResult, String> {
+    let library = ToolLibrary::load(&get_tool_library_file_path()?)?;
+    let shapes = dxf_export::select_shapes(&part_model, part_index)?;
+    machining_simulation::simulate_machining(shapes, &library, &options.unwrap_or_default())
+}
+
+enum MeshFormat {
+    Stl,
+    Gltf,
+}
+
+// Writes the machined panel as STL, GLB or glTF and returns the size of the mesh
+fn write_panel_mesh(
+    path: &str,
+    part_model: &PartModel,
+    part_index: Option<usize>,
+    options: Option<SimulationOptions>,
+    format: MeshFormat,
+) -> Result<MeshSummary, String> {
+    let resolved_path = if path.starts_with("./") {
+        let project_root = get_project_root()?;
+        project_root.join(&path[2..])
+    } else {
+        PathBuf::from(path)
+    };
+
+    let library = ToolLibrary::load(&get_tool_library_file_path()?)?;
+    let shapes = dxf_export::select_shapes(part_model, part_index)?;
+    let mesh = panel_mesh::mesh_machined_panel(shapes, &library, &options.unwrap_or_default())?;
+    let name = resolved_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("panel");
+    let contents = match format {
+        MeshFormat::Stl => mesh.to_stl(name),
+        MeshFormat::Gltf if resolved_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glb")) => mesh.to_glb(name),
+        MeshFormat::Gltf => mesh.to_gltf(name).into_bytes(),
+    };
+    fs::write(&resolved_path, contents)
+        .map_err(|e| format!("Failed to write mesh file: {}", e))?;
+    Ok(mesh.summary())
+}
+
+#[tauri::command]
+fn save_stl(
+    path: String,
+    part_model: PartModel,
+    part_index: Option<usize>,
+    options: Option<SimulationOptions>,
+) -> Result<MeshSummary, String> {
+    write_panel_mesh(&path, &part_model, part_index, options, MeshFormat::Stl)
+}
+
+// A .glb path gets the binary container, anything else a .gltf with the buffer embedded
+#[tauri::command]
+fn export_gltf(
+    path: String,
+    part_model: PartModel,
+    part_index: Option<usize>,
+    options: Option<SimulationOptions>,
+) -> Result<MeshSummary, String> {
+    write_panel_mesh(&path, &part_model, part_index, options, MeshFormat::Gltf)
+}
+
+#[tauri::command]
+fn check_model(
+    part_model: PartModel,
+    parameters: Option<ModelParameters>,
+    product_mode: Option<bool>,
+) -> Result<ModelCheckReport, String> {
+    let product_mode = product_mode.unwrap_or(false);
+    let parameters = parameters.unwrap_or_else(|| {
+        if product_mode { ModelParameters::product_defaults() } else { ModelParameters::default() }
+    });
+    parameters.validate()?;
+    let library = ToolLibrary::load(&get_tool_library_file_path()?)?;
+    Ok(model_check::check_model(&part_model, &parameters, product_mode, &library))
+}
+
+#[derive(serde::Serialize)]
+struct ShapeOutline {
+    layer_name: String,
+    face: Option<PanelFace>,
+    closed: bool,
+    length: f64,
+    area: Option<f64>, // Signed, positive for counter-clockwise shapes; only for closed shapes
+    bounds: Option<Bounds>,
+    points: Vec<Point>,
+}
+
+// Bulge arcs flattened for the preview, within chord_tolerance of the exact arcs
+#[tauri::command]
+fn tessellate_shapes(
+    part_model: PartModel,
+    part_index: Option<usize>,
+    chord_tolerance: Option<f64>,
+) -> Result<Vec<ShapeOutline>, String> {
+    let chord_tolerance = chord_tolerance.unwrap_or(0.01);
+    if chord_tolerance.is_nan() || chord_tolerance <= 0.0 {
+        return Err(format!("Chord tolerance must be positive, got {}", chord_tolerance));
+    }
+    let shapes = dxf_export::select_shapes(&part_model, part_index)?;
+    Ok(shapes.iter().map(|shape| {
+        let polyline = shape.polyline();
+        let closed = polyline.is_closed();
+        ShapeOutline {
+            layer_name: shape.layer_name.clone(),
+            face: shape.face,
+            closed,
+            length: polyline.length(),
+            area: closed.then(|| polyline.area()),
+            bounds: polyline.bounds(),
+            points: polyline.tessellate(chord_tolerance),
+        }
+    }).collect())
+}
+
+#[tauri::command]
+fn analyze_layer(layer_name: String) -> LayerToolSpec {
+    layer_tool::parse_layer_name(&layer_name)
 }
 
 #[tauri::command]
@@ -639,6 +963,7 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(LuaRunRegistry::default())
         .invoke_handler(tauri::generate_handler![
             read_file,
             write_file,
@@ -655,6 +980,24 @@ fn main() {
             save_settings,
             get_lua_library_path,
             execute_lua_script,
+            cancel_lua_execution,
+            sweep_model,
+            export_dxf,
+            export_svg,
+            export_gcode,
+            analyze_layer,
+            simulate_machining,
+            save_stl,
+            export_gltf,
+            check_model,
+            tessellate_shapes,
+            get_cnc_tools,
+            add_cnc_tool,
+            update_cnc_tool,
+            delete_cnc_tool,
+            import_cnc_tools,
+            export_cnc_tools,
+            resolve_cnc_tool,
             check_lua_availability,
             validate_lua_syntax
         ])
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use crate::geometry::{Bounds, Point, Segment};
+use crate::layer_tool::LayerOperation;
+use crate::lua_engine::ModelParameters;
+use crate::machining_simulation::PANEL_LAYER;
+use crate::part_model::{ModelShape, PanelFace, PartModel, ShapeNode};
+use crate::tool_check;
+use crate::tool_library::ToolLibrary;
+
+// Distance below which two points are the same point
+const POINT_TOLERANCE: f64 = 0.001;
+const BULGE_TOLERANCE: f64 = 1e-6;
+
+// Largest distance between a bulge arc and the chords it is checked with
+const CHORD_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckRule {
+    SelfIntersection,
+    OpenContour,
+    OutsidePanel,
+    ThroughDepth,
+    DuplicateShape,
+    NarrowFeature,
+    SharpInnerCorner,
+    VBitTooDeep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelDiagnostic {
+    pub rule: CheckRule,
+    pub severity: Severity,
+    pub part_name: Option<String>, // Set in product mode
+    pub shape_index: usize,        // 1-based like ADekoLib's data table
+    pub layer_name: String,
+    pub message: String,
+    pub location: Option<[f64; 2]>, // Where on the face the problem is
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelCheckReport {
+    pub shape_count: usize,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub diagnostics: Vec<ModelDiagnostic>,
+}
+
+/// Runs the design rules over every shape of a run, and checks each shape against the tool the
+/// library assigns its layer. In product mode each part is checked against its own size and the door
+/// or body material thickness.
+pub fn check_model(part_model: &PartModel, parameters: &ModelParameters, product_mode: bool, library: &ToolLibrary) -> ModelCheckReport {
+    let mut diagnostics = Vec::new();
+    let mut shape_count = 0;
+
+    if product_mode {
+        for part in &part_model.parts {
+            let thickness = if part.is_door { parameters.door_material_thickness } else { parameters.body_material_thickness };
+            let panel = PanelGeometry { width: part.width, height: part.height, thickness };
+            shape_count += part.shapes.len();
+            check_shapes(&part.shapes, panel, parameters, library, Some(&part.name), &mut diagnostics);
+        }
+    } else {
+        let (width, height) = parameters.panel_size();
+        let panel = PanelGeometry { width, height, thickness: parameters.material_thickness };
+        shape_count += part_model.shapes.len();
+        check_shapes(&part_model.shapes, panel, parameters, library, None, &mut diagnostics);
+    }
+
+    ModelCheckReport {
+        shape_count,
+        error_count: diagnostics.iter().filter(|d| d.severity == Severity::Error).count(),
+        warning_count: diagnostics.iter().filter(|d| d.severity == Severity::Warning).count(),
+        diagnostics,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PanelGeometry {
+    width: f64,
+    height: f64,
+    thickness: f64,
+}
+
+fn check_shapes(
+    shapes: &[ModelShape],
+    panel: PanelGeometry,
+    parameters: &ModelParameters,
+    library: &ToolLibrary,
+    part_name: Option<&str>,
+    diagnostics: &mut Vec<ModelDiagnostic>,
+) {
+    let mut report = |index: usize, rule: CheckRule, severity: Severity, message: String, location: Option<[f64; 2]>| {
+        diagnostics.push(ModelDiagnostic {
+            rule,
+            severity,
+            part_name: part_name.map(str::to_string),
+            shape_index: index + 1,
+            layer_name: shapes[index].layer_name.clone(),
+            message,
+            location,
+        });
+    };
+
+    // Edge bands and annotation layers are not cut
+    let is_checked = |shape: &ModelShape| {
+        shape.tool_spec.is_machined()
+            && !shape.nodes.is_empty()
+            && !parameters.edge_bands.iter().any(|edge_band| edge_band.layer_name == shape.layer_name)
+    };
+
+    for (index, shape) in shapes.iter().enumerate() {
+        if !is_checked(shape) {
+            continue;
+        }
+
+        if let Some(point) = self_intersection(shape) {
+            report(index, CheckRule::SelfIntersection, Severity::Error,
+                format!("Path crosses itself at ({:.3}, {:.3})", point.x, point.y), Some([point.x, point.y]));
+        }
+
+        if is_contour_layer(shape) && !shape.is_closed() {
+            let last = shape.nodes[shape.nodes.len() - 1];
+            report(index, CheckRule::OpenContour, Severity::Error,
+                format!("Contour is not closed, it ends at ({:.3}, {:.3})", last.x, last.y), Some([last.x, last.y]));
+        }
+
+        if let Some(face) = shape.face {
+            let (face_width, face_height) = face.extent(panel.width, panel.height, panel.thickness);
+            // Arcs can bulge out of the panel between nodes that are inside it
+            let outside = shape.polyline().tessellate(CHORD_TOLERANCE).into_iter().find(|point| {
+                point.x < -POINT_TOLERANCE
+                    || point.y < -POINT_TOLERANCE
+                    || point.x > face_width + POINT_TOLERANCE
+                    || point.y > face_height + POINT_TOLERANCE
+            });
+            if let Some(point) = outside {
+                report(index, CheckRule::OutsidePanel, Severity::Error,
+                    format!("Point ({:.3}, {:.3}) is outside the {} x {} {} face", point.x, point.y, face_width, face_height, face.name()),
+                    Some([point.x, point.y]));
+            }
+        }
+
+        // Only the top and bottom faces are cut into the material thickness, and the panel outline
+        // is cut through on purpose
+        let cuts_into_thickness = matches!(shape.face, Some(PanelFace::Top | PanelFace::Bottom)) && shape.layer_name != PANEL_LAYER;
+        if let Some((depth, location)) = shape.deepest_point().filter(|_| cuts_into_thickness) {
+            if depth > panel.thickness + POINT_TOLERANCE {
+                report(index, CheckRule::ThroughDepth, Severity::Error,
+                    format!("Depth {} is beyond the material thickness {}", depth, panel.thickness), Some(location));
+            } else if depth >= panel.thickness - POINT_TOLERANCE {
+                report(index, CheckRule::ThroughDepth, Severity::Warning,
+                    format!("Depth {} cuts through the material thickness {}", depth, panel.thickness), Some(location));
+            }
+        }
+
+        // Each stack of duplicates is reported once, on every shape after the first
+        if let Some(original) = (0..index).find(|&other| is_checked(&shapes[other]) && is_duplicate(&shapes[other], shape)) {
+            let first = shape.nodes[0];
+            report(index, CheckRule::DuplicateShape, Severity::Warning,
+                format!("Same path as shape {} on the same layer and face", original + 1), Some([first.x, first.y]));
+        }
+
+        for finding in tool_check::check_shape_tool(shape, library) {
+            report(index, finding.rule, finding.severity, finding.message, Some(finding.location));
+        }
+    }
+}
+
+// Layers whose tool follows a closed outline
+fn is_contour_layer(shape: &ModelShape) -> bool {
+    matches!(
+        shape.tool_spec.operation,
+        LayerOperation::Contouring | LayerOperation::InnerContouring | LayerOperation::OuterContouring | LayerOperation::Pocketing
+    ) || shape.layer_name == PANEL_LAYER
+}
+
+// Same layer, face and nodes, in the same or the reverse direction
+fn is_duplicate(a: &ModelShape, b: &ModelShape) -> bool {
+    if a.layer_name != b.layer_name || a.face != b.face || a.nodes.len() != b.nodes.len() {
+        return false;
+    }
+    let same = |p: ShapeNode, q: ShapeNode| (p.x - q.x).abs() <= POINT_TOLERANCE && (p.y - q.y).abs() <= POINT_TOLERANCE;
+    let n = a.nodes.len();
+
+    let forward = (0..n).all(|i| {
+        same(a.nodes[i], b.nodes[i]) && (i + 1 == n || (a.nodes[i].bulge - b.nodes[i].bulge).abs() <= BULGE_TOLERANCE)
+    });
+    // Reversing a path reverses the direction of its arcs, the bulge of a segment moves to its other end
+    let reverse = (0..n).all(|i| {
+        same(a.nodes[i], b.nodes[n - 1 - i]) && (i + 1 == n || (a.nodes[i].bulge + b.nodes[n - 2 - i].bulge).abs() <= BULGE_TOLERANCE)
+    });
+    forward || reverse
+}
+
+/// First point where two segments of the path that do not follow each other meet, bulge arcs included.
+fn self_intersection(shape: &ModelShape) -> Option<Point> {
+    let mut points: Vec<Point> = Vec::new();
+    for point in shape.polyline().tessellate(CHORD_TOLERANCE) {
+        if points.last().is_none_or(|last| last.distance(point) > POINT_TOLERANCE) {
+            points.push(point);
+        }
+    }
+    let segment_count = points.len().saturating_sub(1);
+    let closed = segment_count > 2 && points[0].distance(points[segment_count]) <= POINT_TOLERANCE;
+    let bounds: Vec<Bounds> = points.windows(2).map(|pair| Segment::line(pair[0], pair[1]).bounds()).collect();
+
+    for i in 0..segment_count {
+        for j in i + 2..segment_count {
+            // In a closed path the last segment follows the first one
+            if closed && i == 0 && j == segment_count - 1 {
+                continue;
+            }
+            let (a, b) = (bounds[i], bounds[j]);
+            if a.max.x < b.min.x - POINT_TOLERANCE
+                || b.max.x < a.min.x - POINT_TOLERANCE
+                || a.max.y < b.min.y - POINT_TOLERANCE
+                || b.max.y < a.min.y - POINT_TOLERANCE
+            {
+                continue;
+            }
+            if let Some(point) = segment_intersection(points[i], points[i + 1], points[j], points[j + 1]) {
+                return Some(point);
+            }
+        }
+    }
+    None
+}
+
+// Intersection point of segments ab and cd, touching ends and overlaps included
+fn segment_intersection(a: Point, b: Point, c: Point, d: Point) -> Option<Point> {
+    let (r, s) = (b - a, d - c);
+    let denominator = r.cross(s);
+
+    if denominator.abs() <= 1e-12 * r.length() * s.length() {
+        // Parallel, they only meet when collinear and overlapping
+        if r.cross(c - a).abs() > POINT_TOLERANCE * r.length() {
+            return None;
+        }
+        let project = |p: Point| (p - a).dot(r) / r.dot(r);
+        let (tc, td) = (project(c), project(d));
+        let t = tc.min(td).max(0.0);
+        return (t <= tc.max(td).min(1.0)).then(|| a + r * t);
+    }
+
+    let t = (c - a).cross(s) / denominator;
+    let u = (c - a).cross(r) / denominator;
+    let (t_tolerance, u_tolerance) = (POINT_TOLERANCE / r.length(), POINT_TOLERANCE / s.length());
+    ((-t_tolerance..=1.0 + t_tolerance).contains(&t) && (-u_tolerance..=1.0 + u_tolerance).contains(&u)).then(|| a + r * t)
+}
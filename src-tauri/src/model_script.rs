@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::Path;
+use crate::source_map::{ScriptBuilder, SourceMap, SourceOrigin};
+
+/// How the user script is wrapped before it is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptMode {
+    Plain,
+    Debug,   // ADekoDebugMode.lua calls modelMain()
+    Product, // ADekoProductMode.lua calls productMain()
+}
+
+impl ScriptMode {
+    /// Product mode takes precedence over debug mode.
+    pub fn from_flags(debug_mode: bool, product_mode: bool) -> Self {
+        if product_mode {
+            ScriptMode::Product
+        } else if debug_mode {
+            ScriptMode::Debug
+        } else {
+            ScriptMode::Plain
+        }
+    }
+}
+
+/// Prepares the script content with library includes if needed.
+/// The builder records which lines come from the user script, so errors can be mapped back.
+pub fn build_model_script(script_content: &str, script_name: &str, lua_library_path: &str, mode: ScriptMode) -> (String, SourceMap) {
+    let mut full_script = ScriptBuilder::new();
+
+    // AdekoLib.lua is now bundled with the app and loaded automatically by the Lua engine
+    eprintln!("=== USING BUNDLED ADEKO LIB ===");
+    eprintln!("AdekoLib.lua is bundled with the app and loaded automatically");
+    eprintln!("No need to load from external files");
+
+    // Skip loading external turtle.lua - we use built-in turtle functions
+    eprintln!("=== USING BUILT-IN TURTLE FUNCTIONS ===");
+    eprintln!("Skipping external turtle.lua (uses wxWidgets which is not available)");
+    eprintln!("Using built-in turtle graphics implementation instead");
+
+    // Open turtle graphics window using built-in function
+    full_script.push_str("-- Using built-in turtle graphics\n");
+    full_script.push_str("-- open('Lua Debug - Turtle Graphics')\n");
+
+    if mode == ScriptMode::Product {
+        // Product mode mirrors debug mode, but ADekoProductMode.lua builds the parts list through productMain()
+        eprintln!("📦 Product mode: Running productMain() with ADekoProductMode.lua");
+
+        full_script.push_str("\n-- ===== USER SCRIPT (defines productMain function) =====\n");
+        full_script.begin(SourceOrigin::UserScript, script_name);
+        full_script.push_str(script_content);
+        full_script.begin(SourceOrigin::Generated, "<generated>");
+        full_script.push_str("\n\n");
+
+        let product_mode_path = Path::new(lua_library_path).join("ADekoProductMode.lua");
+        eprintln!("📁 Attempting to load ADekoProductMode.lua from: {}", product_mode_path.display());
+
+        match fs::read_to_string(&product_mode_path) {
+            Ok(product_content) => {
+                eprintln!("✅ Successfully loaded ADekoProductMode.lua ({} bytes)", product_content.len());
+                full_script.push_str("-- ===== ADEKOLIB PRODUCT MODE =====\n");
+                full_script.begin(SourceOrigin::ProductModePrelude, "ADekoProductMode.lua");
+                full_script.push_str(&product_content);
+                full_script.begin(SourceOrigin::Generated, "<generated>");
+                full_script.push_str("\nprint('✅ Product mode execution completed')\n");
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to read ADekoProductMode.lua: {}", e);
+                full_script.begin(SourceOrigin::FallbackBlock, "<product fallback>");
+                full_script.push_str("-- ===== FALLBACK PRODUCT ENVIRONMENT =====\n");
+                full_script.push_str("print('⚠️  ADekoProductMode.lua not found, using simplified product environment')\n");
+                full_script.push_str("ADekoLib.start()\n");
+                full_script.push_str("ADekoLib.startProduct()\n");
+                full_script.push_str("if productMain then\n");
+                full_script.push_str("  productMain()\n");
+                full_script.push_str("else\n");
+                full_script.push_str("  print('❌ productMain function not found in script')\n");
+                full_script.push_str("end\n");
+                full_script.push_str("ADekoLib.finish()\n");
+                full_script.push_str("ADekoLib.finishProduct()\n");
+            }
+        }
+    } else if mode == ScriptMode::Debug {
+        // In debug mode, we emulate ZeroBrane's behavior:
+        // 1. First load the user script to define modelMain()
+        // 2. Then execute ADekoDebugMode.lua which sets up 6-face layout and calls modelMain()
+        eprintln!("🔧 Debug mode: Emulating ZeroBrane-style debugging");
+
+        // Add the user script which defines modelMain()
+        full_script.push_str("\n-- ===== USER SCRIPT (defines modelMain function) =====\n");
+        full_script.begin(SourceOrigin::UserScript, script_name);
+        full_script.push_str(script_content);
+        full_script.begin(SourceOrigin::Generated, "<generated>");
+        full_script.push_str("\n\n");
+
+        // Load ADekoDebugMode.lua from file (will be packaged with the app)
+        eprintln!("📋 Debug mode: Loading ADekoDebugMode.lua for 6-face layout");
+        let debug_mode_path = Path::new(lua_library_path).join("ADekoDebugMode.lua");
+        eprintln!("📁 Attempting to load ADekoDebugMode.lua from: {}", debug_mode_path.display());
+
+        if debug_mode_path.exists() {
+            match fs::read_to_string(&debug_mode_path) {
+                Ok(debug_content) => {
+                    eprintln!("✅ Successfully loaded ADekoDebugMode.lua ({} bytes)", debug_content.len());
+                    full_script.push_str("-- ===== ADEKOLIB DEBUG MODE (ZeroBrane-style 6-face layout) =====\n");
+                    full_script.push_str("print('🎯 Starting ZeroBrane-style debug mode with 6-face layout')\n");
+                    full_script.begin(SourceOrigin::DebugModePrelude, "ADekoDebugMode.lua");
+                    full_script.push_str(&debug_content);
+                    full_script.begin(SourceOrigin::Generated, "<generated>");
+                    full_script.push_str("\nprint('✅ Debug mode execution completed')\n");
+                }
+                Err(e) => {
+                    eprintln!("❌ Failed to read ADekoDebugMode.lua: {}", e);
+                    // Enhanced fallback with better ZeroBrane emulation
+                    full_script.begin(SourceOrigin::FallbackBlock, "<debug fallback>");
+                    full_script.push_str("-- ===== FALLBACK DEBUG ENVIRONMENT =====\n");
+                    full_script.push_str("print('⚠️  Using fallback debug environment (ADekoDebugMode.lua not found)')\n");
+                    full_script.push_str("print('🔧 Setting up basic debug environment')\n");
+                    full_script.push_str("if ADekoLib then ADekoLib.start() end\n");
+                    full_script.push_str("if modelMain then\n");
+                    full_script.push_str("  print('📞 Calling modelMain() function')\n");
+                    full_script.push_str("  modelMain()\n");
+                    full_script.push_str("  print('✅ modelMain() execution completed')\n");
+                    full_script.push_str("else\n");
+                    full_script.push_str("  print('❌ modelMain function not found in script')\n");
+                    full_script.push_str("end\n");
+                    full_script.push_str("if ADekoLib then ADekoLib.finish() end\n");
+                }
+            }
+        } else {
+            eprintln!("❌ ADekoDebugMode.lua not found at: {}", debug_mode_path.display());
+            // Enhanced fallback with better ZeroBrane emulation
+            full_script.begin(SourceOrigin::FallbackBlock, "<debug fallback>");
+            full_script.push_str("-- ===== SIMPLIFIED DEBUG ENVIRONMENT =====\n");
+            full_script.push_str("print('⚠️  ADekoDebugMode.lua not found, using simplified debug environment')\n");
+            full_script.push_str("print('🔧 Setting up basic debug environment')\n");
+            full_script.push_str("if ADekoLib then ADekoLib.start() end\n");
+            full_script.push_str("if modelMain then\n");
+            full_script.push_str("  print('📞 Calling modelMain() function')\n");
+            full_script.push_str("  modelMain()\n");
+            full_script.push_str("  print('✅ modelMain() execution completed')\n");
+            full_script.push_str("else\n");
+            full_script.push_str("  print('❌ modelMain function not found in script')\n");
+            full_script.push_str("end\n");
+            full_script.push_str("if ADekoLib then ADekoLib.finish() end\n");
+        }
+
+        eprintln!("Debug mode: Final script length: {}", full_script.script().len());
+
+        // Debug: Show the last few lines of the script to check for syntax issues
+        let lines: Vec<&str> = full_script.script().lines().collect();
+        let total_lines = lines.len();
+        eprintln!("Debug mode: Total lines in final script: {}", total_lines);
+        if total_lines > 10 {
+            eprintln!("Debug mode: Last 10 lines of script:");
+            for (i, line) in lines.iter().enumerate().skip(total_lines - 10) {
+                eprintln!("  {}: {}", i + 1, line);
+            }
+        }
+    } else {
+        // Normal mode: just add the user script
+        full_script.push_str("\n-- User Script:\n");
+        full_script.begin(SourceOrigin::UserScript, script_name);
+        full_script.push_str(script_content);
+    }
+
+
+    full_script.finish()
+}
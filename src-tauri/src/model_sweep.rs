@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use crate::lua_engine::{ExecutionLimits, ExecutionStatus, ModelParameters, NativeLuaEngine, ResourceLimits};
+use crate::machining_simulation::PANEL_LAYER;
+use crate::part_model::{ModelShape, PanelFace, PartModel};
+use crate::source_map::SourceMap;
+
+// Keeps an accidental huge grid from running for hours
+const MAX_SWEEP_CASES: usize = 10_000;
+
+// Shapes may touch the panel border, coordinates are in mm
+const OUTSIDE_PANEL_TOLERANCE: f64 = 0.001;
+
+/// Parameter combinations to run a model with. Every non-empty axis is combined with all others,
+/// empty axes keep the value of `base`; `cases` are run as given after the grid.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelSweep {
+    pub base: Option<ModelParameters>,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub material_thickness: Vec<f64>,
+    pub edge_thickness: Vec<f64>, // Applied to all four edge bands
+    pub model_parameters: Vec<String>,
+    pub cases: Vec<ModelParameters>,
+}
+
+impl ModelSweep {
+    pub fn combinations(&self, base: &ModelParameters) -> Vec<ModelParameters> {
+        let has_grid = !(self.x.is_empty()
+            && self.y.is_empty()
+            && self.material_thickness.is_empty()
+            && self.edge_thickness.is_empty()
+            && self.model_parameters.is_empty());
+
+        let mut combinations = Vec::new();
+        if has_grid || self.cases.is_empty() {
+            combinations.push(base.clone());
+            combinations = expand(combinations, &self.x, |p, &x| p.x = x);
+            combinations = expand(combinations, &self.y, |p, &y| p.y = y);
+            combinations = expand(combinations, &self.material_thickness, |p, &t| {
+                p.material_thickness = t;
+                p.body_material_thickness = t;
+                p.door_material_thickness = t;
+            });
+            combinations = expand(combinations, &self.edge_thickness, |p, &t| {
+                for edge_band in p.edge_bands.iter_mut() {
+                    edge_band.thickness = t;
+                }
+            });
+            combinations = expand(combinations, &self.model_parameters, |p, s| p.model_parameters = s.clone());
+        }
+        combinations.extend(self.cases.iter().cloned());
+        combinations
+    }
+}
+
+fn expand<T>(combinations: Vec<ModelParameters>, values: &[T], apply: impl Fn(&mut ModelParameters, &T)) -> Vec<ModelParameters> {
+    if values.is_empty() {
+        return combinations;
+    }
+
+    let mut expanded = Vec::with_capacity(combinations.len() * values.len());
+    for parameters in &combinations {
+        for value in values {
+            let mut parameters = parameters.clone();
+            apply(&mut parameters, value);
+            expanded.push(parameters);
+        }
+    }
+    expanded
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepIssue {
+    Error,
+    NoShapes,
+    OutsidePanel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutsidePanelShape {
+    pub part_name: Option<String>, // Set in product mode
+    pub shape_index: usize,        // 1-based like ADekoLib's data table
+    pub layer_name: String,
+    pub face: PanelFace,
+    pub min: [f64; 2],
+    pub max: [f64; 2],
+    pub face_size: [f64; 2],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepCaseResult {
+    pub index: usize,
+    pub parameters: ModelParameters,
+    pub status: ExecutionStatus,
+    pub error: Option<String>,
+    pub shape_count: usize,
+    pub outside_panel: Vec<OutsidePanelShape>,
+    pub issues: Vec<SweepIssue>,
+    pub execution_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepReport {
+    pub total_cases: usize,
+    pub completed_cases: usize, // Less than total_cases when the sweep was cancelled
+    pub failed_cases: usize,    // Cases with at least one issue
+    pub cases: Vec<SweepCaseResult>,
+}
+
+/// Runs an assembled model script once per parameter combination, each in a fresh engine.
+pub fn run_sweep(
+    script: &str,
+    source_map: &SourceMap,
+    lua_library_path: Option<String>,
+    product_mode: bool,
+    sweep: &ModelSweep,
+    limits: &ExecutionLimits,
+    resource_limits: ResourceLimits,
+) -> Result<SweepReport, String> {
+    let base = sweep.base.clone().unwrap_or_else(|| {
+        if product_mode { ModelParameters::product_defaults() } else { ModelParameters::default() }
+    });
+    let combinations = sweep.combinations(&base);
+    if combinations.len() > MAX_SWEEP_CASES {
+        return Err(format!("Sweep has {} cases, the limit is {}", combinations.len(), MAX_SWEEP_CASES));
+    }
+    for parameters in &combinations {
+        parameters.validate()?;
+    }
+
+    let mut cases = Vec::with_capacity(combinations.len());
+    for (index, parameters) in combinations.iter().enumerate() {
+        if limits.cancel_flag.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+            break;
+        }
+
+        let engine = NativeLuaEngine::new_with_resource_limits(lua_library_path.clone(), resource_limits)
+            .map_err(|e| format!("Failed to create Lua engine: {}", e))?;
+        engine.set_source_map(source_map.clone());
+        engine.set_model_parameters(parameters)
+            .map_err(|e| format!("Failed to set model parameters: {}", e))?;
+
+        let result = engine.execute_script_with_limits(script, limits);
+        let part_model = result.part_model.unwrap_or_default();
+        let shape_count = if product_mode {
+            part_model.parts.iter().map(|part| part.shapes.len()).sum()
+        } else {
+            machining_shape_count(&part_model, parameters)
+        };
+        let outside_panel = outside_panel_shapes(&part_model, parameters, product_mode);
+
+        let mut issues = Vec::new();
+        if !result.success {
+            issues.push(SweepIssue::Error);
+        }
+        if shape_count == 0 {
+            issues.push(SweepIssue::NoShapes);
+        }
+        if !outside_panel.is_empty() {
+            issues.push(SweepIssue::OutsidePanel);
+        }
+
+        cases.push(SweepCaseResult {
+            index,
+            parameters: parameters.clone(),
+            status: result.status,
+            error: (!result.success).then_some(result.error),
+            shape_count,
+            outside_panel,
+            issues,
+            execution_time_ms: result.execution_time_ms,
+        });
+    }
+
+    Ok(SweepReport {
+        total_cases: combinations.len(),
+        completed_cases: cases.len(),
+        failed_cases: cases.iter().filter(|case| !case.issues.is_empty()).count(),
+        cases,
+    })
+}
+
+// ADekoLib.start() creates the edge band shapes and makePart the panel outline on their own,
+// they do not count as model output
+fn machining_shape_count(part_model: &PartModel, parameters: &ModelParameters) -> usize {
+    part_model.shapes.iter()
+        .filter(|shape| shape.layer_name != PANEL_LAYER)
+        .filter(|shape| !parameters.edge_bands.iter().any(|edge_band| edge_band.layer_name == shape.layer_name))
+        .count()
+}
+
+fn outside_panel_shapes(part_model: &PartModel, parameters: &ModelParameters, product_mode: bool) -> Vec<OutsidePanelShape> {
+    let mut outside = Vec::new();
+
+    if product_mode {
+        for part in &part_model.parts {
+            let thickness = if part.is_door { parameters.door_material_thickness } else { parameters.body_material_thickness };
+            collect_outside(&part.shapes, (part.width, part.height, thickness), Some(&part.name), &mut outside);
+        }
+    } else {
+        let (width, height) = parameters.panel_size();
+        collect_outside(&part_model.shapes, (width, height, parameters.material_thickness), None, &mut outside);
+    }
+
+    outside
+}
+
+fn collect_outside(shapes: &[ModelShape], panel: (f64, f64, f64), part_name: Option<&str>, outside: &mut Vec<OutsidePanelShape>) {
+    for (index, shape) in shapes.iter().enumerate() {
+        // Faces rotated by a model direction have no fixed extent to check against
+        let (Some(face), Some(bounds)) = (shape.face, shape.bounds()) else {
+            continue;
+        };
+        let (min, max) = ([bounds.min.x, bounds.min.y], [bounds.max.x, bounds.max.y]);
+
+        let (face_width, face_height) = face.extent(panel.0, panel.1, panel.2);
+        let inside = min[0] >= -OUTSIDE_PANEL_TOLERANCE
+            && min[1] >= -OUTSIDE_PANEL_TOLERANCE
+            && max[0] <= face_width + OUTSIDE_PANEL_TOLERANCE
+            && max[1] <= face_height + OUTSIDE_PANEL_TOLERANCE;
+
+        if !inside {
+            outside.push(OutsidePanelShape {
+                part_name: part_name.map(str::to_string),
+                shape_index: index + 1,
+                layer_name: shape.layer_name.clone(),
+                face,
+                min,
+                max,
+                face_size: [face_width, face_height],
+            });
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::f64::consts::{PI, TAU};
+use crate::geometry::{Arc, Bounds, Point, Polyline, Segment, Vertex};
+
+// Distance below which two points are the same point
+const POINT_TOLERANCE: f64 = 1e-4;
+
+// Cross products of unit directions below this are a straight joint
+const TURN_TOLERANCE: f64 = 1e-9;
+
+// Convex corners whose sharp join would reach further than this many offset distances from the
+// corner are rounded instead
+const MITER_LIMIT: f64 = 10.0;
+
+/// Offsets a polyline by `distance` to the right of its direction, like ADekoLib.offSet: outwards
+/// for counter-clockwise polylines when positive. The polyline is taken as closed. Corners get sharp
+/// joins and arcs stay arcs; every part of the offset that comes closer than `distance` to the
+/// polyline is cut away, which can leave several loops or none at all. The loops run in the
+/// direction of the polyline and repeat their first vertex at the end.
+pub fn offset_polyline(polyline: &Polyline, distance: f64) -> Vec<Polyline> {
+    let segments = closed_segments(polyline);
+    if segments.len() < 2 {
+        return Vec::new();
+    }
+    if distance.abs() <= POINT_TOLERANCE {
+        return vec![to_polyline(segments)];
+    }
+
+    let (raw, wedges) = raw_offset(&segments, distance);
+
+    // The offset is the edge of the area the polyline sweeps moved up to `distance` sideways, with
+    // the wedges of the sharp joins added: pieces within that area are cut away
+    let near_segments = BoundsGrid::new(segments.iter().map(|segment| segment.bounds()), distance.abs());
+    let near_wedges = BoundsGrid::new(wedges.iter().filter_map(|wedge| wedge.bounds()), 0.0);
+    let is_clear = |point: Point| {
+        let within_distance = |index: usize| point.distance(segments[index].closest_point(point)) < distance.abs() - POINT_TOLERANCE;
+        let within_wedge = |index: usize| {
+            let wedge: &Polyline = &wedges[index];
+            wedge.contains(point) && wedge.segments().all(|side| point.distance(side.closest_point(point)) > POINT_TOLERANCE)
+        };
+        let bounds = Bounds::from_point(point);
+        !near_segments.near(bounds).any(within_distance) && !near_wedges.near(bounds).any(within_wedge)
+    };
+    let pieces: Vec<Segment> = split_at_intersections(&raw).into_iter().filter(|piece| is_clear(piece.point_at(0.5))).collect();
+    stitch_loops(&pieces)
+        .into_iter()
+        .filter(|result| result.area().abs() > POINT_TOLERANCE)
+        .collect()
+}
+
+// Segments of the polyline closed back to its first vertex, without zero-length segments
+fn closed_segments(polyline: &Polyline) -> Vec<Segment> {
+    let mut vertices: Vec<Vertex> = Vec::new();
+    for vertex in &polyline.vertices {
+        match vertices.last_mut() {
+            // The bulge of a repeated vertex is the one of the segment leaving it
+            Some(last) if last.point.distance(vertex.point) <= POINT_TOLERANCE => last.bulge = vertex.bulge,
+            _ => vertices.push(*vertex),
+        }
+    }
+    // ADekoLib closes a polyline by repeating its first vertex
+    if vertices.len() > 1 && vertices[0].point.distance(vertices[vertices.len() - 1].point) <= POINT_TOLERANCE {
+        vertices.pop();
+    }
+    let count = vertices.len();
+    (0..count).map(|i| Segment::new(vertices[i].point, vertices[(i + 1) % count].point, vertices[i].bulge)).collect()
+}
+
+fn to_polyline(segments: Vec<Segment>) -> Polyline {
+    let mut vertices: Vec<Vertex> = segments.iter().map(|segment| Vertex { point: segment.start, bulge: segment.bulge }).collect();
+    if let Some(first) = vertices.first() {
+        vertices.push(Vertex { point: first.point, bulge: 0.0 });
+    }
+    Polyline::new(vertices)
+}
+
+// The segment moved `distance` to its right, `None` for an arc that shrinks to its center
+fn offset_segment(segment: &Segment, distance: f64) -> Option<Segment> {
+    match segment.arc() {
+        Some(arc) => {
+            // The right of a counter-clockwise arc is away from its center
+            let radius = arc.radius + distance * arc.sweep.signum();
+            (radius > POINT_TOLERANCE).then(|| Segment::from_arc(&Arc { radius, ..arc }))
+        }
+        None => {
+            let right = -(segment.end - segment.start).normalized().perpendicular() * distance;
+            Some(Segment::line(segment.start + right, segment.end + right))
+        }
+    }
+}
+
+/// Offsets every segment and joins them into one closed path: convex corners get a sharp join, or
+/// an arc around the corner when the offsets never meet, and overlapping offsets at concave corners
+/// are cut back to where they cross. The path still crosses itself wherever the offset is too large
+/// for the polyline. Returns the path and the wedges between each sharp join and its corner.
+fn raw_offset(segments: &[Segment], distance: f64) -> (Vec<Segment>, Vec<Polyline>) {
+    let offsets: Vec<(usize, Segment)> = segments
+        .iter()
+        .enumerate()
+        .filter_map(|(index, segment)| offset_segment(segment, distance).map(|offset| (index, offset)))
+        .collect();
+    let count = offsets.len();
+
+    // Ends of each offset segment once joined to its neighbours, and what links it to the next one
+    let mut starts: Vec<Point> = offsets.iter().map(|(_, offset)| offset.start).collect();
+    let mut ends: Vec<Point> = offsets.iter().map(|(_, offset)| offset.end).collect();
+    let mut links: Vec<Option<Segment>> = vec![None; count];
+    let mut wedges = Vec::new();
+
+    for k in 0..count {
+        let next = (k + 1) % count;
+        let ((index, a), (next_index, b)) = (offsets[k], offsets[next]);
+        if a.end.distance(b.start) <= POINT_TOLERANCE {
+            continue;
+        }
+        // Arcs that shrank away leave no corner to join at
+        if (index + 1) % segments.len() != next_index {
+            links[k] = Some(Segment::line(a.end, b.start));
+            continue;
+        }
+
+        let corner = segments[next_index].start;
+        let (incoming, outgoing) = (segments[index].end_direction(), segments[next_index].start_direction());
+        let turn = incoming.cross(outgoing);
+        let nearest = |points: Vec<Point>| points.into_iter().min_by(|p, q| p.distance(corner).total_cmp(&q.distance(corner)));
+
+        if turn * distance > 0.0 || (turn.abs() <= TURN_TOLERANCE && incoming.dot(outgoing) < 0.0) {
+            // Convex: extend both offsets until they meet
+            let miter = nearest(
+                a.extended_intersections(&b)
+                    .into_iter()
+                    .filter(|&point| point.distance(corner) <= MITER_LIMIT * distance.abs())
+                    .filter(|&point| a.parameter(point) >= 1.0 - TURN_TOLERANCE && b.parameter(point) <= TURN_TOLERANCE)
+                    .collect(),
+            );
+            match miter {
+                Some(point) => {
+                    (ends[k], starts[next]) = (point, point);
+                    // The extensions of both offsets run along their own line or circle
+                    let extension_bulge = |segment: &Segment, from: f64, to: f64| (segment.sweep() * (to - from) / 4.0).tan();
+                    wedges.push(Polyline::new(vec![
+                        Vertex { point: corner, bulge: 0.0 },
+                        Vertex { point: a.end, bulge: extension_bulge(&a, 1.0, a.parameter(point)) },
+                        Vertex { point, bulge: extension_bulge(&b, b.parameter(point), 0.0) },
+                        Vertex { point: b.start, bulge: 0.0 },
+                        Vertex { point: corner, bulge: 0.0 },
+                    ]));
+                }
+                None => links[k] = Some(round_join(a.end, b.start, corner, distance)),
+            }
+        } else {
+            // Concave: the offsets cross near the corner, or are left for the clean-up when they are too short
+            match nearest(a.intersections(&b, POINT_TOLERANCE)) {
+                Some(point) => (ends[k], starts[next]) = (point, point),
+                None => links[k] = Some(Segment::line(a.end, b.start)),
+            }
+        }
+    }
+
+    let mut raw = Vec::with_capacity(count * 2);
+    for (k, (_, offset)) in offsets.iter().enumerate() {
+        raw.push(trimmed(offset, starts[k], ends[k]));
+        raw.extend(links[k]);
+    }
+    (raw, wedges)
+}
+
+// Arc of radius |distance| around the corner from one offset to the next, turning with the offset side
+fn round_join(from: Point, to: Point, corner: Point, distance: f64) -> Segment {
+    let (u, v) = (from - corner, to - corner);
+    let mut sweep = u.cross(v).atan2(u.dot(v));
+    if sweep * distance < 0.0 {
+        sweep += TAU * distance.signum();
+    }
+    Segment::new(from, to, (sweep / 4.0).tan())
+}
+
+// The offset segment with its ends moved along its line or circle; ends that passed each other
+// are linked straight, the clean-up removes that part
+fn trimmed(segment: &Segment, start: Point, end: Point) -> Segment {
+    let (from, to) = (segment.parameter(start), segment.parameter(end));
+    if to <= from || !segment.is_arc() {
+        return Segment::line(start, end);
+    }
+    Segment::new(start, end, (segment.sweep() * (to - from) / 4.0).tan())
+}
+
+/// Splits the raw offset where it crosses itself. The pieces keep the order of the path.
+fn split_at_intersections(raw: &[Segment]) -> Vec<Segment> {
+    let count = raw.len();
+    let mut splits: Vec<Vec<(f64, Point)>> = raw.iter().map(|segment| vec![(0.0, segment.start), (1.0, segment.end)]).collect();
+    let bounds: Vec<Bounds> = raw.iter().map(|segment| segment.bounds()).collect();
+    let grid = BoundsGrid::new(bounds.iter().copied(), POINT_TOLERANCE);
+    // Last segment each one was tested against, so pairs sharing several cells are tested once
+    let mut tested = vec![usize::MAX; count];
+
+    for i in 0..count {
+        for j in grid.near(bounds[i]) {
+            if j <= i || tested[j] == i {
+                continue;
+            }
+            tested[j] = i;
+            let (a, b) = (bounds[i], bounds[j]);
+            if a.max.x < b.min.x - POINT_TOLERANCE
+                || b.max.x < a.min.x - POINT_TOLERANCE
+                || a.max.y < b.min.y - POINT_TOLERANCE
+                || b.max.y < a.min.y - POINT_TOLERANCE
+            {
+                continue;
+            }
+            for point in raw[i].intersections(&raw[j], POINT_TOLERANCE) {
+                // Neighbours meet where one ends and the next starts
+                let joint = (j == i + 1 && point.distance(raw[i].end) <= POINT_TOLERANCE)
+                    || (i == 0 && j == count - 1 && point.distance(raw[0].start) <= POINT_TOLERANCE);
+                if !joint {
+                    splits[i].push((raw[i].parameter(point).clamp(0.0, 1.0), point));
+                    splits[j].push((raw[j].parameter(point).clamp(0.0, 1.0), point));
+                }
+            }
+        }
+    }
+
+    let mut pieces = Vec::new();
+    for (segment, mut points) in raw.iter().zip(splits) {
+        points.sort_by(|p, q| p.0.total_cmp(&q.0));
+        points.dedup_by(|next, previous| next.1.distance(previous.1) <= POINT_TOLERANCE);
+        for pair in points.windows(2) {
+            let ((from, start), (to, end)) = (pair[0], pair[1]);
+            let bulge = if segment.is_arc() { (segment.sweep() * (to - from) / 4.0).tan() } else { 0.0 };
+            pieces.push(Segment::new(start, end, bulge));
+        }
+    }
+    pieces
+}
+
+/// Chains pieces end to start into closed loops, merging pieces that continue the same line or arc.
+/// Chains that never close are dropped.
+fn stitch_loops(pieces: &[Segment]) -> Vec<Polyline> {
+    let count = pieces.len();
+    let mut used = vec![false; count];
+    let mut loops = Vec::new();
+    // Pieces by the X of their start, to find where the path continues
+    let mut by_start: Vec<usize> = (0..count).collect();
+    by_start.sort_by(|&p, &q| pieces[p].start.x.total_cmp(&pieces[q].start.x));
+
+    for first in 0..count {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let mut chain = vec![first];
+        let closed = loop {
+            let last = chain[chain.len() - 1];
+            let end = pieces[last].end;
+            if end.distance(pieces[first].start) <= POINT_TOLERANCE {
+                break true;
+            }
+            // The piece following in path order continues the loop unless the path crossed itself here
+            let starts_at_end = |k: usize| !used[k] && pieces[k].start.distance(end) <= POINT_TOLERANCE;
+            let following = (last + 1) % count;
+            let next = if starts_at_end(following) {
+                Some(following)
+            } else {
+                let first_candidate = by_start.partition_point(|&k| pieces[k].start.x < end.x - POINT_TOLERANCE);
+                by_start[first_candidate..]
+                    .iter()
+                    .copied()
+                    .take_while(|&k| pieces[k].start.x <= end.x + POINT_TOLERANCE)
+                    .find(|&k| starts_at_end(k))
+            };
+            match next {
+                Some(k) => {
+                    used[k] = true;
+                    chain.push(k);
+                }
+                None => break false,
+            }
+        };
+        if !closed {
+            continue;
+        }
+
+        let mut segments: Vec<Segment> = Vec::new();
+        for &k in &chain {
+            match segments.last().and_then(|previous| merged(previous, &pieces[k])) {
+                Some(segment) => *segments.last_mut().unwrap() = segment,
+                None => segments.push(pieces[k]),
+            }
+        }
+        if let Some(segment) = merged(&segments[segments.len() - 1], &segments[0]).filter(|_| segments.len() > 2) {
+            segments.pop();
+            segments[0] = segment;
+        }
+        loops.push(to_polyline(segments));
+    }
+    loops
+}
+
+// One segment for two that follow each other on the same line, or on the same circle up to a
+// half circle so that a loop never becomes a single full circle
+fn merged(previous: &Segment, next: &Segment) -> Option<Segment> {
+    match (previous.arc(), next.arc()) {
+        (None, None) => {
+            let (u, v) = ((previous.end - previous.start).normalized(), (next.end - next.start).normalized());
+            (u.cross(v).abs() <= TURN_TOLERANCE && u.dot(v) > 0.0).then(|| Segment::line(previous.start, next.end))
+        }
+        (Some(a), Some(b)) => {
+            let sweep = a.sweep + b.sweep;
+            let same_circle = a.center.distance(b.center) <= POINT_TOLERANCE && (a.radius - b.radius).abs() <= POINT_TOLERANCE;
+            (same_circle && a.sweep * b.sweep > 0.0 && sweep.abs() <= PI + TURN_TOLERANCE)
+                .then(|| Segment::new(previous.start, next.end, (sweep / 4.0).tan()))
+        }
+        _ => None,
+    }
+}
+
+/// Uniform grid over bounds grown by a margin, for finding the segments or areas that can come
+/// within that margin of a point or of another segment.
+struct BoundsGrid {
+    origin: Point,
+    cell_size: f64,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl BoundsGrid {
+    fn new(bounds: impl IntoIterator<Item = Bounds>, margin: f64) -> Self {
+        let grown: Vec<Bounds> = bounds
+            .into_iter()
+            .map(|bounds| Bounds { min: bounds.min - Point::new(margin, margin), max: bounds.max + Point::new(margin, margin) })
+            .collect();
+        let all = grown.iter().copied().reduce(Bounds::union).unwrap_or(Bounds::from_point(Point::default()));
+        let size = all.max - all.min;
+        // About one entry per cell along the longer side
+        let per_side = (grown.len() as f64).sqrt().ceil().max(1.0);
+        let cell_size = (size.x.max(size.y) / per_side).max(POINT_TOLERANCE);
+        let mut grid = Self {
+            origin: all.min,
+            cell_size,
+            columns: (size.x / cell_size) as usize + 1,
+            rows: (size.y / cell_size) as usize + 1,
+            cells: Vec::new(),
+        };
+        grid.cells = vec![Vec::new(); grid.columns * grid.rows];
+        for (index, bounds) in grown.into_iter().enumerate() {
+            let cells: Vec<usize> = grid.cells_of(bounds).collect();
+            for cell in cells {
+                grid.cells[cell].push(index);
+            }
+        }
+        grid
+    }
+
+    fn cells_of(&self, bounds: Bounds) -> impl Iterator<Item = usize> + '_ {
+        let cell = |value: f64, origin: f64, count: usize| (((value - origin) / self.cell_size).max(0.0) as usize).min(count - 1);
+        let (first_column, last_column) = (cell(bounds.min.x, self.origin.x, self.columns), cell(bounds.max.x, self.origin.x, self.columns));
+        let (first_row, last_row) = (cell(bounds.min.y, self.origin.y, self.rows), cell(bounds.max.y, self.origin.y, self.rows));
+        (first_row..=last_row).flat_map(move |row| (first_column..=last_column).map(move |column| row * self.columns + column))
+    }
+
+    /// Entries whose grown bounds can overlap `bounds`, some of them more than once.
+    fn near(&self, bounds: Bounds) -> impl Iterator<Item = usize> + '_ {
+        self.cells_of(bounds).flat_map(move |cell| self.cells[cell].iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA_TOLERANCE: f64 = 1e-6;
+
+    fn polyline(points: &[(f64, f64, f64)]) -> Polyline {
+        Polyline::new(points.iter().map(|&(x, y, bulge)| Vertex { point: Point::new(x, y), bulge }).collect())
+    }
+
+    fn square() -> Polyline {
+        polyline(&[(0.0, 0.0, 0.0), (100.0, 0.0, 0.0), (100.0, 100.0, 0.0), (0.0, 100.0, 0.0), (0.0, 0.0, 0.0)])
+    }
+
+    // 100 x 100 with corners of radius 10, counter-clockwise
+    fn rounded_rectangle() -> Polyline {
+        let bulge = (PI / 8.0).tan();
+        polyline(&[
+            (10.0, 0.0, 0.0), (90.0, 0.0, bulge), (100.0, 10.0, 0.0), (100.0, 90.0, bulge),
+            (90.0, 100.0, 0.0), (10.0, 100.0, bulge), (0.0, 90.0, 0.0), (0.0, 10.0, bulge), (10.0, 0.0, 0.0),
+        ])
+    }
+
+    fn areas(loops: &[Polyline]) -> Vec<f64> {
+        loops.iter().map(Polyline::area).collect()
+    }
+
+    #[test]
+    fn square_grows_with_sharp_corners_and_shrinks() {
+        let outer = offset_polyline(&square(), 10.0);
+        assert_eq!(outer.len(), 1);
+        assert!(outer[0].is_closed());
+        assert_eq!(outer[0].vertices.len(), 5);
+        assert!((outer[0].area() - 120.0 * 120.0).abs() < AREA_TOLERANCE);
+
+        let inner = offset_polyline(&square(), -10.0);
+        assert_eq!(inner.len(), 1);
+        assert!((inner[0].area() - 80.0 * 80.0).abs() < AREA_TOLERANCE);
+    }
+
+    #[test]
+    fn clockwise_square_grows_with_negative_distance() {
+        let mut clockwise = square();
+        clockwise.vertices.reverse();
+        let outer = offset_polyline(&clockwise, -10.0);
+        assert_eq!(outer.len(), 1);
+        assert!((outer[0].area() + 120.0 * 120.0).abs() < AREA_TOLERANCE);
+    }
+
+    #[test]
+    fn rounded_rectangle_keeps_its_arcs() {
+        for (distance, radius, side) in [(5.0, 15.0, 110.0), (-5.0, 5.0, 90.0)] {
+            let loops = offset_polyline(&rounded_rectangle(), distance);
+            assert_eq!(loops.len(), 1);
+            assert_eq!(loops[0].segments().filter(Segment::is_arc).count(), 4);
+            let expected = side * side - (4.0 - PI) * radius * radius;
+            assert!((loops[0].area() - expected).abs() < AREA_TOLERANCE, "{} for {}", loops[0].area(), distance);
+        }
+    }
+
+    #[test]
+    fn offsets_past_the_middle_collapse() {
+        assert!(offset_polyline(&square(), -50.0).is_empty());
+        assert!(offset_polyline(&square(), -60.0).is_empty());
+        assert!(offset_polyline(&rounded_rectangle(), -55.0).is_empty());
+
+        let circle = polyline(&[(0.0, 0.0, 1.0), (20.0, 0.0, 1.0), (0.0, 0.0, 0.0)]);
+        assert!(offset_polyline(&circle, -12.0).is_empty());
+    }
+
+    #[test]
+    fn self_intersecting_offsets_are_trimmed() {
+        // Two 100 x 100 squares joined by a 10 wide neck, which closes when shrunk by more than 5
+        let dumbbell = polyline(&[
+            (0.0, 0.0, 0.0), (100.0, 0.0, 0.0), (100.0, 45.0, 0.0), (150.0, 45.0, 0.0), (150.0, 0.0, 0.0),
+            (250.0, 0.0, 0.0), (250.0, 100.0, 0.0), (150.0, 100.0, 0.0), (150.0, 55.0, 0.0), (100.0, 55.0, 0.0),
+            (100.0, 100.0, 0.0), (0.0, 100.0, 0.0),
+        ]);
+        let halves = offset_polyline(&dumbbell, -6.0);
+        assert_eq!(halves.len(), 2);
+        for area in areas(&halves) {
+            assert!((area - 88.0 * 88.0).abs() < AREA_TOLERANCE, "{}", area);
+        }
+
+        // A 10 wide notch fills up when grown by more than 5
+        let notched = polyline(&[
+            (0.0, 0.0, 0.0), (45.0, 0.0, 0.0), (45.0, 50.0, 0.0), (55.0, 50.0, 0.0), (55.0, 0.0, 0.0),
+            (100.0, 0.0, 0.0), (100.0, 100.0, 0.0), (0.0, 100.0, 0.0),
+        ]);
+        let grown = offset_polyline(&notched, 6.0);
+        assert_eq!(grown.len(), 1);
+        assert!((grown[0].area() - 112.0 * 112.0).abs() < AREA_TOLERANCE, "{}", grown[0].area());
+
+        // Every other point of a dense circle lies 0.5 inside, the miters of that zigzag overlap
+        let zigzag: Vec<(f64, f64, f64)> = (0..2000)
+            .map(|i| {
+                let angle = i as f64 / 2000.0 * TAU;
+                let radius = if i % 2 == 0 { 1000.0 } else { 999.5 };
+                (radius * angle.cos(), radius * angle.sin(), 0.0)
+            })
+            .collect();
+        let zigzag = polyline(&zigzag);
+        let shrunk = offset_polyline(&zigzag, -3.0);
+        assert_eq!(shrunk.len(), 1);
+        let segments = closed_segments(&zigzag);
+        let distance = |point: Point| segments.iter().map(|segment| point.distance(segment.closest_point(point))).fold(f64::MAX, f64::min);
+        assert!(shrunk[0].vertices.iter().all(|vertex| distance(vertex.point) > 3.0 - POINT_TOLERANCE));
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use crate::geometry::Point;
+use crate::machining_simulation::{self, Heightmap, SimulationOptions, PANEL_LAYER};
+use crate::part_model::ModelShape;
+use crate::tool_library::ToolLibrary;
+
+// Cells cut this close to the full thickness are holes in the panel
+const THROUGH_CUT_TOLERANCE: f64 = 1e-3;
+
+// Wood-like base color for the glTF material
+const PANEL_COLOR: [f64; 4] = [0.85, 0.72, 0.52, 1.0];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TriangleMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MeshSummary {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+}
+
+impl TriangleMesh {
+    pub fn summary(&self) -> MeshSummary {
+        MeshSummary { vertex_count: self.positions.len(), triangle_count: self.triangles.len() }
+    }
+
+    /// Binary STL, the format slicers and most CAD viewers read fastest.
+    pub fn to_stl(&self, name: &str) -> Vec<u8> {
+        let mut stl = Vec::with_capacity(84 + self.triangles.len() * 50);
+        let mut header = [0u8; 80];
+        for (byte, source) in header.iter_mut().zip(name.bytes()) {
+            *byte = source;
+        }
+        stl.extend_from_slice(&header);
+        stl.extend_from_slice(&(self.triangles.len() as u32).to_le_bytes());
+
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|index| self.positions[index as usize]);
+            for value in face_normal(a, b, c).iter().chain(a.iter()).chain(b.iter()).chain(c.iter()) {
+                stl.extend_from_slice(&value.to_le_bytes());
+            }
+            stl.extend_from_slice(&0u16.to_le_bytes());
+        }
+        stl
+    }
+
+    /// glTF 2.0 as a single binary .glb file.
+    pub fn to_glb(&self, name: &str) -> Vec<u8> {
+        let (json, buffer) = self.gltf_parts(name, None);
+        let json = pad(json.into_bytes(), b' ');
+        let buffer = pad(buffer, 0);
+
+        let mut glb = Vec::with_capacity(12 + 8 + json.len() + 8 + buffer.len());
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + buffer.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&buffer);
+        glb
+    }
+
+    /// glTF 2.0 JSON with the geometry embedded as a base64 data URI.
+    pub fn to_gltf(&self, name: &str) -> String {
+        let (_, buffer) = self.gltf_parts(name, None);
+        let uri = format!("data:application/octet-stream;base64,{}", base64(&buffer));
+        self.gltf_parts(name, Some(uri)).0
+    }
+
+    fn gltf_parts(&self, name: &str, buffer_uri: Option<String>) -> (String, Vec<u8>) {
+        let mut buffer = Vec::with_capacity(self.positions.len() * 12 + self.triangles.len() * 12);
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in &self.positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+                buffer.extend_from_slice(&position[axis].to_le_bytes());
+            }
+        }
+        let positions_length = buffer.len();
+        for index in self.triangles.iter().flatten() {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+        let indices_length = buffer.len() - positions_length;
+        if self.positions.is_empty() {
+            (min, max) = ([0.0; 3], [0.0; 3]);
+        }
+
+        let mut buffer_json = serde_json::json!({ "byteLength": buffer.len() });
+        if let Some(uri) = buffer_uri {
+            buffer_json["uri"] = uri.into();
+        }
+
+        // Panel coordinates are Z up in mm, glTF is Y up in meters
+        let json = serde_json::json!({
+            "asset": { "version": "2.0", "generator": "Lua Macro Editor" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{
+                "name": name,
+                "mesh": 0,
+                "matrix": [0.001, 0.0, 0.0, 0.0, 0.0, 0.0, -0.001, 0.0, 0.0, 0.001, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            }],
+            "meshes": [{
+                "name": name,
+                "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }],
+            }],
+            "materials": [{
+                "name": "Panel",
+                "pbrMetallicRoughness": { "baseColorFactor": PANEL_COLOR, "metallicFactor": 0.0, "roughnessFactor": 0.8 },
+            }],
+            "buffers": [buffer_json],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": positions_length, "target": 34962 },
+                { "buffer": 0, "byteOffset": positions_length, "byteLength": indices_length, "target": 34963 },
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": self.positions.len(), "type": "VEC3", "min": min, "max": max },
+                { "bufferView": 1, "componentType": 5125, "count": self.triangles.len() * 3, "type": "SCALAR" },
+            ],
+        });
+        (json.to_string(), buffer)
+    }
+}
+
+/// Simulates the machining of `shapes` and meshes the resulting panel inside its PANEL outline.
+pub fn mesh_machined_panel(shapes: &[ModelShape], library: &ToolLibrary, options: &SimulationOptions) -> Result<TriangleMesh, String> {
+    let simulation = machining_simulation::simulate_machining(shapes, library, options)?;
+    // With an explicit panel size the heightmap is the whole panel, the outline would not line up with it
+    let outline = if options.panel_width.is_some() && options.panel_height.is_some() {
+        None
+    } else {
+        shapes.iter().find(|shape| shape.layer_name == PANEL_LAYER && shape.face == Some(options.face))
+    };
+    Ok(build_panel_mesh(&simulation.heightmap, outline, options.material_thickness))
+}
+
+/// Builds a closed mesh of the machined panel from a simulated heightmap: every heightmap cell inside
+/// the outline becomes a column from Z0 up to the remaining material, with walls along the outline and
+/// around through cuts. The top surface runs through the cell corners, each at the lowest adjacent cell.
+pub fn build_panel_mesh(heightmap: &Heightmap, outline: Option<&ModelShape>, material_thickness: f64) -> TriangleMesh {
+    let (columns, rows, cell_size) = (heightmap.columns, heightmap.rows, heightmap.cell_size);
+    let outline = outline.map(|shape| shape.polyline()).filter(|outline| outline.vertices.len() > 2);
+
+    let mut inside = vec![false; columns * rows];
+    for row in 0..rows {
+        for column in 0..columns {
+            let height = heightmap.heights[row * columns + column] as f64;
+            let center = Point::new(
+                heightmap.origin[0] + (column as f64 + 0.5) * cell_size,
+                heightmap.origin[1] + (row as f64 + 0.5) * cell_size,
+            );
+            inside[row * columns + column] = height > -material_thickness + THROUGH_CUT_TOLERANCE
+                && outline.as_ref().is_none_or(|outline| outline.contains(center));
+        }
+    }
+    let is_inside = |column: isize, row: isize| {
+        column >= 0 && row >= 0 && (column as usize) < columns && (row as usize) < rows
+            && inside[row as usize * columns + column as usize]
+    };
+
+    // Corner heights from the lowest inside cell around them
+    let corner_columns = columns + 1;
+    let mut corner_heights = vec![f64::MAX; corner_columns * (rows + 1)];
+    for row in 0..rows {
+        for column in 0..columns {
+            if !inside[row * columns + column] {
+                continue;
+            }
+            let height = heightmap.heights[row * columns + column] as f64;
+            for (dc, dr) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+                let corner = &mut corner_heights[(row + dr) * corner_columns + column + dc];
+                *corner = corner.min(height);
+            }
+        }
+    }
+
+    let mut mesh = TriangleMesh::default();
+    let mut top_vertices = vec![u32::MAX; corner_heights.len()];
+    let mut bottom_vertices = vec![u32::MAX; corner_heights.len()];
+    let mut vertex = |mesh: &mut TriangleMesh, column: usize, row: usize, top: bool| -> u32 {
+        let corner = row * corner_columns + column;
+        let slot = if top { &mut top_vertices[corner] } else { &mut bottom_vertices[corner] };
+        if *slot == u32::MAX {
+            *slot = mesh.positions.len() as u32;
+            mesh.positions.push([
+                (heightmap.origin[0] + column as f64 * cell_size) as f32,
+                (heightmap.origin[1] + row as f64 * cell_size) as f32,
+                if top { (material_thickness + corner_heights[corner]) as f32 } else { 0.0 },
+            ]);
+        }
+        *slot
+    };
+
+    for row in 0..rows {
+        for column in 0..columns {
+            if !inside[row * columns + column] {
+                continue;
+            }
+            // Corners counter-clockwise seen from above
+            let corners = [(column, row), (column + 1, row), (column + 1, row + 1), (column, row + 1)];
+            let top = corners.map(|(c, r)| vertex(&mut mesh, c, r, true));
+            let bottom = corners.map(|(c, r)| vertex(&mut mesh, c, r, false));
+            mesh.triangles.push([top[0], top[1], top[2]]);
+            mesh.triangles.push([top[0], top[2], top[3]]);
+            mesh.triangles.push([bottom[0], bottom[2], bottom[1]]);
+            mesh.triangles.push([bottom[0], bottom[3], bottom[2]]);
+
+            // Walls face away from the cell, towards the neighbour that is not part of the panel
+            let (c, r) = (column as isize, row as isize);
+            let neighbours = [(c, r - 1), (c + 1, r), (c, r + 1), (c - 1, r)];
+            for (edge, (nc, nr)) in neighbours.into_iter().enumerate() {
+                if is_inside(nc, nr) {
+                    continue;
+                }
+                let (p, q) = (edge, (edge + 1) % 4);
+                mesh.triangles.push([bottom[p], bottom[q], top[q]]);
+                mesh.triangles.push([bottom[p], top[q], top[p]]);
+            }
+        }
+    }
+    mesh
+}
+
+fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if length > 0.0 { n.map(|value| value / length) } else { [0.0; 3] }
+}
+
+// GLB chunks are 4-byte aligned
+fn pad(mut bytes: Vec<u8>, filler: u8) -> Vec<u8> {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(filler);
+    }
+    bytes
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for (i, shift) in [18, 12, 6, 0].into_iter().enumerate() {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(value >> shift & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
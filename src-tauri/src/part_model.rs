@@ -0,0 +1,274 @@
+use mlua::{Result as LuaResult, Table, Value};
+use serde::{Deserialize, Serialize};
+use crate::geometry::{Bounds, Point, Polyline, Vertex};
+use crate::layer_tool::{parse_layer_name, LayerToolSpec};
+
+// Rotation and translation ADekoLib assigns to shapes on the top face
+const TOP_FACE_ROTATION: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+const TOP_FACE_TRANSLATION: [f64; 3] = [0.0, 0.0, 0.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelFace {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    Rear,
+    Front,
+}
+
+impl PanelFace {
+    // Rotation matrices set by ADekoLib.setFace, see ADekoLib.start()
+    const ROTATIONS: [(PanelFace, [[f64; 3]; 3]); 6] = [
+        (PanelFace::Top, TOP_FACE_ROTATION),
+        (PanelFace::Bottom, [[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]]),
+        (PanelFace::Left, [[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]]),
+        (PanelFace::Right, [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]]),
+        (PanelFace::Rear, [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]]),
+        (PanelFace::Front, [[0.0, 1.0, 0.0], [0.0, 0.0, -1.0], [-1.0, 0.0, 0.0]]),
+    ];
+
+    /// Face with exactly this rotation, `None` for rotations combined with a model direction.
+    pub fn from_rotation(rotation: &[[f64; 3]; 3]) -> Option<Self> {
+        Self::ROTATIONS.iter().find(|(_, r)| r == rotation).map(|(face, _)| *face)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PanelFace::Top => "top",
+            PanelFace::Bottom => "bottom",
+            PanelFace::Left => "left",
+            PanelFace::Right => "right",
+            PanelFace::Rear => "rear",
+            PanelFace::Front => "front",
+        }
+    }
+
+    /// Size of the face in its own X/Y coordinates for a panel of width x height x thickness,
+    /// matching the face layout drawn by ADekoDebugMode.lua.
+    pub fn extent(self, width: f64, height: f64, thickness: f64) -> (f64, f64) {
+        match self {
+            PanelFace::Top | PanelFace::Bottom => (width, height),
+            PanelFace::Left => (height, thickness),
+            PanelFace::Right => (thickness, height),
+            PanelFace::Rear => (width, thickness),
+            PanelFace::Front => (thickness, width),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ShapeNode {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub bulge: f64, // Bulge of the segment starting at this node, 0 for a straight segment
+}
+
+/// One entry of ADekoLib's `data` table: a polyline on a layer with its depth and face.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelShape {
+    pub layer_name: String,
+    pub thickness: f64,
+    pub nodes: Vec<ShapeNode>,
+    pub rotation: [[f64; 3]; 3],
+    pub translation: [f64; 3], // Unit translation, scaled by Y/X/materialThickness in ADekoLib.translationOffset
+    pub face: Option<PanelFace>,
+    #[serde(default)]
+    pub tool_spec: LayerToolSpec, // Parsed from layer_name
+}
+
+impl ModelShape {
+    /// The nodes as a geometry polyline, Z left out.
+    pub fn polyline(&self) -> Polyline {
+        Polyline::new(self.nodes.iter().map(|node| Vertex { point: Point::new(node.x, node.y), bulge: node.bulge }).collect())
+    }
+
+    /// Bounding box of the path with its arcs, `None` for a shape without nodes.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.polyline().bounds()
+    }
+
+    /// Cutting depth below the surface; the frontend draws |thickness| as the depth as well.
+    pub fn depth(&self) -> f64 {
+        self.thickness.abs()
+    }
+
+    /// Nodes with Z values give the cutting depth per node instead of the shape thickness.
+    pub fn has_depth_profile(&self) -> bool {
+        self.nodes.iter().any(|node| node.z != 0.0)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.polyline().is_closed()
+    }
+
+    /// Depth and position of the lowest tool tip, `None` for a shape without nodes.
+    pub fn deepest_point(&self) -> Option<(f64, [f64; 2])> {
+        let first = self.nodes.first()?;
+        if !self.has_depth_profile() {
+            return Some((self.depth(), [first.x, first.y]));
+        }
+        Some(self.nodes.iter().fold((-first.z, [first.x, first.y]), |deepest, node| {
+            if -node.z > deepest.0 { (-node.z, [node.x, node.y]) } else { deepest }
+        }))
+    }
+
+    /// Points along the shape at most `step` apart, bulge arcs included. Z is interpolated between nodes.
+    pub fn path_points(&self, step: f64) -> Vec<[f64; 3]> {
+        let Some(first) = self.nodes.first() else {
+            return Vec::new();
+        };
+        let mut points = vec![[first.x, first.y, first.z]];
+        for (pair, segment) in self.nodes.windows(2).zip(self.polyline().segments()) {
+            let (from_z, to_z) = (pair[0].z, pair[1].z);
+            let count = ((segment.length().max((to_z - from_z).abs()) / step).ceil() as usize).max(1);
+            points.extend((1..=count).map(|i| {
+                let t = i as f64 / count as f64;
+                let point = segment.point_at(t);
+                [point.x, point.y, from_z + (to_z - from_z) * t]
+            }));
+        }
+        points
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EdgeBand {
+    pub layer_name: String,
+    pub thickness: f64,
+}
+
+/// One entry of ADekoLib's `parts` table, filled by ADekoLib.packIntoPart in product mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPart {
+    pub name: String,
+    pub description: String,
+    pub material: String,
+    pub width: f64, // Including edge bands, as ADekoLib.partWidth reports it
+    pub height: f64,
+    pub is_door: bool,
+    pub ignore_material_grain: bool,
+    pub edge_bands: Vec<EdgeBand>,
+    pub shapes: Vec<ModelShape>,
+}
+
+/// Machining geometry left behind by a run in the `data` and `parts` globals.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartModel {
+    pub shapes: Vec<ModelShape>,
+    pub parts: Vec<ModelPart>,
+}
+
+impl PartModel {
+    /// Reads the model from the Lua globals, `None` when the script never started ADekoLib.
+    pub fn from_globals(globals: &Table) -> LuaResult<Option<Self>> {
+        let data = match globals.get::<_, Value>("data")? {
+            Value::Table(data) => data,
+            _ => return Ok(None),
+        };
+
+        let parts = match globals.get::<_, Value>("parts")? {
+            Value::Table(parts) => read_parts(&parts)?,
+            _ => Vec::new(),
+        };
+
+        // ADekoLib.finish() clears data[0] and drops the open shape; when a run stops before it,
+        // the last shape is still open and ends with the placeholder node of ADekoLib.nextNode()
+        let mut shapes = read_shapes(&data)?;
+        if data.contains_key(0)? {
+            if let Some(open_shape) = shapes.last_mut() {
+                open_shape.nodes.pop();
+                if open_shape.nodes.is_empty() {
+                    shapes.pop();
+                }
+            }
+        }
+
+        Ok(Some(Self { shapes, parts }))
+    }
+}
+
+// Shapes are stored from index 1, ADekoLib keeps a scratch shape at index 0 until ADekoLib.finish()
+fn read_shapes(data: &Table) -> LuaResult<Vec<ModelShape>> {
+    let mut shapes = Vec::new();
+    for shape in data.clone().sequence_values::<Table>() {
+        let shape = shape?;
+        let mut nodes = Vec::new();
+        for node in shape.clone().sequence_values::<Table>() {
+            let node = node?;
+            nodes.push(ShapeNode {
+                x: node.get::<_, Option<f64>>("X")?.unwrap_or_default(),
+                y: node.get::<_, Option<f64>>("Y")?.unwrap_or_default(),
+                z: node.get::<_, Option<f64>>("Z")?.unwrap_or_default(),
+                bulge: node.get::<_, Option<f64>>("bulge")?.unwrap_or_default(),
+            });
+        }
+
+        let rotation = match shape.get::<_, Option<Table>>("rotation")? {
+            Some(rotation) => [
+                read_vector(&rotation.get::<_, Table>(1)?)?,
+                read_vector(&rotation.get::<_, Table>(2)?)?,
+                read_vector(&rotation.get::<_, Table>(3)?)?,
+            ],
+            None => TOP_FACE_ROTATION,
+        };
+
+        let layer_name = shape.get::<_, Option<String>>("layerName")?.unwrap_or_default();
+        let tool_spec = parse_layer_name(&layer_name);
+        shapes.push(ModelShape {
+            layer_name,
+            thickness: shape.get::<_, Option<f64>>("thickness")?.unwrap_or_default(),
+            nodes,
+            rotation,
+            translation: match shape.get::<_, Option<Table>>("translation")? {
+                Some(translation) => read_vector(&translation)?,
+                None => TOP_FACE_TRANSLATION,
+            },
+            face: PanelFace::from_rotation(&rotation),
+            tool_spec,
+        });
+    }
+    Ok(shapes)
+}
+
+fn read_vector(table: &Table) -> LuaResult<[f64; 3]> {
+    Ok([table.get(1)?, table.get(2)?, table.get(3)?])
+}
+
+fn read_parts(parts: &Table) -> LuaResult<Vec<ModelPart>> {
+    let mut result = Vec::new();
+    for part in parts.clone().sequence_values::<Table>() {
+        let part = part?;
+        let mut edge_bands = Vec::new();
+        for index in 0..4 {
+            edge_bands.push(EdgeBand {
+                layer_name: part.get::<_, Option<String>>(format!("edgeBandLayerName{}", index))?
+                    .unwrap_or_else(|| format!("LMM{}", index)),
+                thickness: part.get::<_, Option<f64>>(format!("edgeBandThickness{}", index))?.unwrap_or_default(),
+            });
+        }
+
+        // The part ADekoLib opens after the last packIntoPart has no shapes until finishProduct removes it
+        let shapes = read_shapes(&part)?;
+        if shapes.is_empty() {
+            continue;
+        }
+
+        // The first shape is the PANEL rectangle whose corners packIntoPart sets to the part size
+        let panel = &shapes[0].nodes;
+        result.push(ModelPart {
+            name: part.get::<_, Option<String>>("partName")?.unwrap_or_default(),
+            description: part.get::<_, Option<String>>("partDescription")?.unwrap_or_default(),
+            material: part.get::<_, Option<String>>("partMaterial")?.unwrap_or_default(),
+            width: panel.get(1).map(|node| node.x).unwrap_or_default(),
+            height: panel.get(3).map(|node| node.y).unwrap_or_default(),
+            is_door: part.get::<_, Option<bool>>("thisPartIsADoor")?.unwrap_or(false),
+            ignore_material_grain: part.get::<_, Option<bool>>("ignoreMaterialGrain")?.unwrap_or(false),
+            edge_bands,
+            shapes,
+        });
+    }
+    Ok(result)
+}
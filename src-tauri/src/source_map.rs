@@ -0,0 +1,122 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Chunk name used when the assembled script is loaded, Lua reports it as "full_script:<line>:"
+pub const SCRIPT_CHUNK_NAME: &str = "=full_script";
+pub const SCRIPT_SHORT_NAME: &str = "full_script";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceOrigin {
+    Generated,
+    UserScript,
+    DebugModePrelude,
+    ProductModePrelude,
+    FallbackBlock,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceSegment {
+    pub origin: SourceOrigin,
+    pub file: String,
+    pub start_line: usize, // First line of the segment in the assembled script (1-based)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub origin: SourceOrigin,
+    pub file: String,
+    pub line: usize,
+}
+
+/// Maps lines of the assembled script back to the segment they came from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceMap {
+    segments: Vec<SourceSegment>,
+}
+
+impl SourceMap {
+    pub fn resolve(&self, line: usize) -> Option<SourceLocation> {
+        let segment = self.segments.iter().rev().find(|s| s.start_line <= line)?;
+        Some(SourceLocation {
+            origin: segment.origin,
+            file: segment.file.clone(),
+            line: line - segment.start_line + 1,
+        })
+    }
+
+    /// Location of the first assembled-script line mentioned in a Lua error message.
+    pub fn first_location(&self, message: &str) -> Option<SourceLocation> {
+        script_line_regex()
+            .captures(message)
+            .and_then(|captures| captures[1].parse::<usize>().ok())
+            .and_then(|line| self.resolve(line))
+    }
+
+    /// Rewrites every "full_script:<line>" reference in an error or traceback to "<file>:<line>".
+    pub fn remap_message(&self, message: &str) -> String {
+        script_line_regex()
+            .replace_all(message, |captures: &regex::Captures| {
+                match captures[1].parse::<usize>().ok().and_then(|line| self.resolve(line)) {
+                    Some(location) => format!("{}:{}", location.file, location.line),
+                    None => captures[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+fn script_line_regex() -> Regex {
+    Regex::new(&format!(r"{}:(\d+)", SCRIPT_SHORT_NAME)).unwrap()
+}
+
+/// Assembles the script that is run by the engine while recording where each part came from.
+pub struct ScriptBuilder {
+    script: String,
+    segments: Vec<SourceSegment>,
+}
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        Self {
+            script: String::new(),
+            segments: vec![SourceSegment {
+                origin: SourceOrigin::Generated,
+                file: "<generated>".to_string(),
+                start_line: 1,
+            }],
+        }
+    }
+
+    /// Starts a new segment; everything pushed afterwards is attributed to `file`.
+    pub fn begin(&mut self, origin: SourceOrigin, file: &str) {
+        // Segments always start on a fresh line so no line is shared between two origins
+        if !self.script.is_empty() && !self.script.ends_with('\n') {
+            self.script.push('\n');
+        }
+
+        self.segments.push(SourceSegment {
+            origin,
+            file: file.to_string(),
+            start_line: self.script.matches('\n').count() + 1,
+        });
+    }
+
+    pub fn push_str(&mut self, content: &str) {
+        self.script.push_str(content);
+    }
+
+    pub fn script(&self) -> &str {
+        &self.script
+    }
+
+    pub fn finish(self) -> (String, SourceMap) {
+        (self.script, SourceMap { segments: self.segments })
+    }
+}
+
+impl Default for ScriptBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,150 @@
+use crate::geometry::{Arc, Bounds, Point};
+use crate::lua_engine::{DrawCommand, DrawStyle};
+
+// Same sizes the preview uses for points and text
+const SVG_POINT_SIZE: f64 = 2.0;
+const SVG_MIN_FONT_SIZE: f64 = 20.0;
+
+/// Builds an SVG drawing of the draw commands with one Inkscape layer group per layer name.
+/// Coordinates are flipped to SVG's y-down axis the same way the preview does it.
+pub fn build_svg(draw_commands: &[DrawCommand]) -> String {
+    let mut layers: Vec<(&str, Vec<String>)> = Vec::new();
+    let mut bounds: Option<Bounds> = None;
+
+    for command in draw_commands {
+        let style = command.style();
+        let (element, element_bounds) = svg_element(command);
+        bounds = Some(match bounds {
+            Some(bounds) => bounds.union(element_bounds),
+            None => element_bounds,
+        });
+        match layers.iter_mut().find(|(name, _)| *name == style.layer_name) {
+            Some((_, elements)) => elements.push(element),
+            None => layers.push((style.layer_name.as_str(), vec![element])),
+        }
+    }
+
+    let margin = draw_commands.iter().map(|c| c.style().size).fold(1.0, f64::max);
+    let (x, y, width, height) = match bounds {
+        Some(Bounds { min, max }) => (
+            min.x - margin,
+            min.y - margin,
+            max.x - min.x + 2.0 * margin,
+            max.y - min.y + 2.0 * margin,
+        ),
+        None => (0.0, 0.0, 1.0, 1.0),
+    };
+
+    let mut svg = String::new();
+    svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\" \
+         width=\"{w}mm\" height=\"{h}mm\" viewBox=\"{x} {y} {w} {h}\">\n",
+        x = fmt(x), y = fmt(y), w = fmt(width), h = fmt(height)
+    ));
+    for (name, elements) in layers {
+        svg.push_str(&format!(
+            "  <g inkscape:groupmode=\"layer\" inkscape:label=\"{0}\" data-layer=\"{0}\" fill=\"none\">\n",
+            escape(name)
+        ));
+        for element in elements {
+            svg.push_str("    ");
+            svg.push_str(&element);
+            svg.push('\n');
+        }
+        svg.push_str("  </g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// SVG element of a draw command and the bounds it covers, both in the flipped coordinates.
+fn svg_element(command: &DrawCommand) -> (String, Bounds) {
+    match command {
+        DrawCommand::Line { x1, y1, x2, y2, style } => (
+            format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {}/>",
+                fmt(*x1), fmt(-y1), fmt(*x2), fmt(-y2), stroke(style)
+            ),
+            Bounds::from_point(Point::new(*x1, -y1)).union(Bounds::from_point(Point::new(*x2, -y2))),
+        ),
+        DrawCommand::Circle { x1, y1, radius, style } => (
+            format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>", fmt(*x1), fmt(-y1), fmt(*radius), stroke(style)),
+            circle_bounds(Point::new(*x1, -y1), *radius),
+        ),
+        DrawCommand::Arc { x1, y1, radius, start_angle, end_angle, style } => {
+            arc_element(Point::new(*x1, -y1), *radius, *start_angle, *end_angle, style)
+        }
+        DrawCommand::Rectangle { x1, y1, x2, y2, radius, style } => {
+            let (left, top) = (x1.min(*x2), -(y1.max(*y2)));
+            let (width, height) = ((x2 - x1).abs(), (y2 - y1).abs());
+            let corner = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+            (
+                format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\" {}/>",
+                    fmt(left), fmt(top), fmt(width), fmt(height), fmt(corner), stroke(style)
+                ),
+                Bounds { min: Point::new(left, top), max: Point::new(left + width, top + height) },
+            )
+        }
+        DrawCommand::Text { x1, y1, text, style } => {
+            let element = format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"Arial\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\" fill=\"{}\" stroke=\"none\">{}</text>",
+                fmt(*x1), fmt(-y1), fmt(SVG_MIN_FONT_SIZE.max(style.size * 1.5)), escape(&style.color), escape(text)
+            );
+            (element, Bounds::from_point(Point::new(*x1, -y1)))
+        }
+        DrawCommand::Point { x1, y1, style } => {
+            let element = format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{size}\" height=\"{size}\" fill=\"{}\" stroke=\"none\"/>",
+                fmt(x1 - SVG_POINT_SIZE / 2.0), fmt(-y1 - SVG_POINT_SIZE / 2.0), escape(&style.color), size = fmt(SVG_POINT_SIZE)
+            );
+            (element, Bounds::from_point(Point::new(*x1, -y1)))
+        }
+    }
+}
+
+// crcl angles run clockwise on screen once y is flipped, which is SVG's positive sweep direction
+fn arc_element(center: Point, radius: f64, start_angle: f64, end_angle: f64, style: &DrawStyle) -> (String, Bounds) {
+    let sweep = end_angle - start_angle;
+    if sweep.abs() >= 360.0 {
+        return (
+            format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>", fmt(center.x), fmt(center.y), fmt(radius), stroke(style)),
+            circle_bounds(center, radius),
+        );
+    }
+
+    // Like canvas arc(): the arc always runs in the positive direction from start to end
+    let sweep = sweep.rem_euclid(360.0);
+    let arc = Arc { center, radius, start_angle: start_angle.to_radians(), sweep: sweep.to_radians() };
+    let (start, end) = (arc.start(), arc.end());
+
+    let element = format!(
+        "<path d=\"M {} {} A {r} {r} 0 {} 1 {} {}\" {}/>",
+        fmt(start.x), fmt(start.y), if sweep > 180.0 { 1 } else { 0 }, fmt(end.x), fmt(end.y), stroke(style),
+        r = fmt(radius)
+    );
+    (element, arc.bounds())
+}
+
+fn circle_bounds(center: Point, radius: f64) -> Bounds {
+    let extent = Point::new(radius, radius);
+    Bounds { min: center - extent, max: center + extent }
+}
+
+fn stroke(style: &DrawStyle) -> String {
+    format!("stroke=\"{}\" stroke-width=\"{}\"", escape(&style.color), fmt(style.size))
+}
+
+// Keeps the file small, 4 decimals are well below machining precision
+fn fmt(value: f64) -> String {
+    let rounded = (value * 10_000.0).round() / 10_000.0;
+    if rounded == 0.0 { "0".to_string() } else { rounded.to_string() }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,198 @@
+use crate::geometry::{Point, Polyline, Segment};
+use crate::layer_tool::{LayerOperation, ToolShape};
+use crate::model_check::{CheckRule, Severity};
+use crate::part_model::ModelShape;
+use crate::tool_library::ToolLibrary;
+
+// Spacing of the points feature widths are measured from
+const SAMPLE_STEP: f64 = 1.0;
+
+// Largest distance between a bulge arc and the chords widths are measured against
+const CHORD_TOLERANCE: f64 = 0.01;
+
+// Sizes this close to the tool still fit
+const SIZE_TOLERANCE: f64 = 0.01;
+
+// Direction changes below this are a smooth joint, not a corner
+const SMOOTH_TURN_DEGREES: f64 = 1.0;
+
+// Walls within this angle of facing each other bound the width of a feature, walls meeting
+// at a wider angle are a corner
+const FACING_WALLS_DEGREES: f64 = 30.0;
+
+/// A problem found by comparing a shape with its tool, at `location` on the face.
+#[derive(Debug, Clone)]
+pub struct ToolFinding {
+    pub rule: CheckRule,
+    pub severity: Severity,
+    pub message: String,
+    pub location: [f64; 2],
+}
+
+// Side of a closed path the tool cuts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CutSide {
+    Inside,
+    Outside,
+}
+
+/// Checks a shape against the tool the layer-to-tool mapping assigns it: features narrower than the
+/// tool, inner corners tighter than the tool radius and V-bits cutting deeper than their cone.
+pub fn check_shape_tool(shape: &ModelShape, library: &ToolLibrary) -> Vec<ToolFinding> {
+    let mut findings = Vec::new();
+    let tool = library.resolve_for_layer(&shape.layer_name);
+    let (tool_name, tool_shape, diameter) = match (tool, shape.tool_spec.diameter) {
+        (Some(tool), _) => (format!("tool '{}'", tool.name), tool.shape, tool.diameter),
+        // Without a library tool the layer name still gives the size
+        (None, Some(diameter)) => (format!("{} mm tool", diameter), shape.tool_spec.tool_shape.unwrap_or(ToolShape::Cylindrical), diameter),
+        (None, None) => return findings,
+    };
+
+    if let Some((cutting_depth, (depth, location))) = tool.and_then(|tool| tool.v_bit_cutting_depth()).zip(shape.deepest_point()) {
+        if depth > cutting_depth + SIZE_TOLERANCE {
+            findings.push(ToolFinding {
+                rule: CheckRule::VBitTooDeep,
+                severity: Severity::Error,
+                message: format!(
+                    "Depth {} at ({:.3}, {:.3}) is beyond the {:.3} mm the cone of {} cuts",
+                    depth, location[0], location[1], cutting_depth, tool_name
+                ),
+                location,
+            });
+        }
+    }
+
+    // V-bits cut sharp corners and narrow grooves by design
+    let side = match shape.tool_spec.operation {
+        LayerOperation::InnerContouring | LayerOperation::Pocketing => CutSide::Inside,
+        LayerOperation::OuterContouring => CutSide::Outside,
+        _ => return findings,
+    };
+    if tool_shape == ToolShape::Conical || !shape.is_closed() {
+        return findings;
+    }
+
+    let polyline = shape.polyline();
+    let orientation = polyline.area().signum();
+    if orientation == 0.0 {
+        return findings;
+    }
+
+    if side == CutSide::Inside {
+        if let Some((width, location)) = narrowest_width(&closed_polygon(&polyline), orientation) {
+            if width < diameter - SIZE_TOLERANCE {
+                findings.push(ToolFinding {
+                    rule: CheckRule::NarrowFeature,
+                    severity: Severity::Error,
+                    message: format!(
+                        "Feature is {:.3} mm wide at ({:.3}, {:.3}), narrower than the {} mm {}",
+                        width, location.x, location.y, diameter, tool_name
+                    ),
+                    location: [location.x, location.y],
+                });
+            }
+        }
+    }
+
+    for (location, radius) in inner_corners(&polyline, side, orientation) {
+        if radius < diameter / 2.0 - SIZE_TOLERANCE {
+            let corner = if radius == 0.0 { "Sharp inner corner".to_string() } else { format!("Inner corner radius {:.3}", radius) };
+            findings.push(ToolFinding {
+                rule: CheckRule::SharpInnerCorner,
+                severity: Severity::Warning,
+                message: format!(
+                    "{} at ({:.3}, {:.3}) is below the {} mm radius of {}",
+                    corner, location.x, location.y, diameter / 2.0, tool_name
+                ),
+                location: [location.x, location.y],
+            });
+        }
+    }
+    findings
+}
+
+// Tessellated path without repeats and without the closing point
+fn closed_polygon(polyline: &Polyline) -> Vec<Point> {
+    let mut polygon: Vec<Point> = Vec::new();
+    for point in polyline.tessellate(CHORD_TOLERANCE) {
+        if polygon.last().is_none_or(|last| last.distance(point) > SIZE_TOLERANCE) {
+            polygon.push(point);
+        }
+    }
+    if polygon.len() > 1 && polygon[0].distance(polygon[polygon.len() - 1]) <= SIZE_TOLERANCE {
+        polygon.pop();
+    }
+    polygon
+}
+
+/// Smallest distance across the inside of a closed polygon, measured along its edges every
+/// SAMPLE_STEP straight inwards to the first wall, as long as that wall faces back. Returns the
+/// width and the middle of that span.
+fn narrowest_width(polygon: &[Point], orientation: f64) -> Option<(f64, Point)> {
+    let count = polygon.len();
+    let edge = |i: usize| (polygon[i], polygon[(i + 1) % count]);
+    // Inward normal, left of the edge for counter-clockwise polygons
+    let normal = |(a, b): (Point, Point)| (b - a).normalized().perpendicular() * orientation;
+    let facing = FACING_WALLS_DEGREES.to_radians().cos();
+
+    let mut narrowest: Option<(f64, Point)> = None;
+    for i in 0..count {
+        let (a, b) = edge(i);
+        let direction = normal((a, b));
+        let samples = ((a.distance(b) / SAMPLE_STEP).ceil() as usize).max(1);
+
+        for sample in 0..samples {
+            let origin = a.lerp(b, (sample as f64 + 0.5) / samples as f64);
+
+            // First wall the ray origin + t * direction hits, as (t, wall index)
+            let mut hit: Option<(f64, usize)> = None;
+            for j in (0..count).filter(|&j| j != i) {
+                let (c, d) = edge(j);
+                let wall = d - c;
+                let denominator = direction.cross(wall);
+                if denominator.abs() < 1e-12 {
+                    continue;
+                }
+                let t = (c - origin).cross(wall) / denominator;
+                let u = (c - origin).cross(direction) / denominator;
+                if t > SIZE_TOLERANCE && (0.0..=1.0).contains(&u) && hit.is_none_or(|(nearest, _)| t < nearest) {
+                    hit = Some((t, j));
+                }
+            }
+
+            let Some((width, wall)) = hit else {
+                continue;
+            };
+            if direction.dot(normal(edge(wall))) <= -facing && narrowest.is_none_or(|(narrowest, _)| width < narrowest) {
+                narrowest = Some((width, origin + direction * (width / 2.0)));
+            }
+        }
+    }
+    narrowest
+}
+
+/// Corners on the material side of the cut with their radius, 0 for a sharp corner. Inside a pocket
+/// those are the corners turning with the path, around an outer contour the ones turning against it.
+fn inner_corners(polyline: &Polyline, side: CutSide, orientation: f64) -> Vec<(Point, f64)> {
+    let is_inner = |turn: f64| match side {
+        CutSide::Inside => turn * orientation > 0.0,
+        CutSide::Outside => turn * orientation < 0.0,
+    };
+    let segments: Vec<Segment> = polyline.segments().filter(|segment| segment.chord() > SIZE_TOLERANCE).collect();
+
+    let mut corners = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        // Joint with the previous segment, the last one for the start of the path
+        let incoming = segments[(i + segments.len() - 1) % segments.len()].end_direction();
+        let outgoing = segment.start_direction();
+        let turn = incoming.cross(outgoing).atan2(incoming.dot(outgoing));
+        if turn.abs() > SMOOTH_TURN_DEGREES.to_radians() && is_inner(turn) {
+            corners.push((segment.start, 0.0));
+        }
+
+        if let Some(arc) = segment.arc().filter(|arc| is_inner(arc.sweep)) {
+            corners.push((segment.point_at(0.5), arc.radius));
+        }
+    }
+    corners
+}
@@ -0,0 +1,363 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use crate::layer_tool::{parse_layer_name, LayerOperation, ToolShape};
+
+const TOOL_LIBRARY_VERSION: u32 = 1;
+
+// Parameter ranges a tool definition must stay in, lengths are in mm
+const DIAMETER_RANGE: (f64, f64) = (0.1, 100.0);
+const LENGTH_RANGE: (f64, f64) = (1.0, 300.0);
+const TIP_ANGLE_RANGE: (f64, f64) = (1.0, 179.0);
+const FLUTES_RANGE: (u32, u32) = (1, 12);
+const HELIX_ANGLE_RANGE: (f64, f64) = (0.0, 60.0);
+
+// Closest tool the layer resolution accepts, same limits as the frontend's layerToolDetector.ts
+const MAX_DIAMETER_DIFFERENCE: f64 = 3.0;
+const MAX_ANGLE_DIFFERENCE: f64 = 15.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolUnits {
+    Metric,
+    Imperial,
+}
+
+/// One cutter, stored in the same shape as the frontend's CNCTool so tool files can be shared.
+/// The shape decides which of the optional geometry fields are required.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CncTool {
+    pub id: String,
+    pub name: String,
+    pub shape: ToolShape,
+    pub units: ToolUnits,
+    pub diameter: f64,
+    pub length: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flutes: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub helix_angle: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tip_angle: Option<f64>, // Conical
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tip_diameter: Option<f64>, // Conical
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ball_radius: Option<f64>, // Ballnose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corner_radius: Option<f64>, // Radial
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub special_type: Option<String>, // Special, e.g. dovetail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_parameters: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coating: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layer_names: Vec<String>, // Layers always cut with this tool, checked before the layer name is parsed
+}
+
+impl CncTool {
+    fn new(id: &str, name: &str, shape: ToolShape, diameter: f64, length: f64) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            shape,
+            units: ToolUnits::Metric,
+            diameter,
+            length,
+            flutes: None,
+            helix_angle: None,
+            tip_angle: None,
+            tip_diameter: None,
+            ball_radius: None,
+            corner_radius: None,
+            special_type: None,
+            custom_parameters: None,
+            profile: None,
+            description: None,
+            material: None,
+            coating: None,
+            manufacturer: None,
+            part_number: None,
+            layer_names: Vec::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("Tool id must not be empty".to_string());
+        }
+        if self.name.trim().is_empty() {
+            return Err(format!("Tool '{}' has no name", self.id));
+        }
+        check_range(&self.id, "diameter", self.diameter, DIAMETER_RANGE)?;
+        check_range(&self.id, "length", self.length, LENGTH_RANGE)?;
+        if let Some(flutes) = self.flutes {
+            if !(FLUTES_RANGE.0..=FLUTES_RANGE.1).contains(&flutes) {
+                return Err(format!(
+                    "Tool '{}': flutes must be between {} and {}, got {}",
+                    self.id, FLUTES_RANGE.0, FLUTES_RANGE.1, flutes
+                ));
+            }
+        }
+        if let Some(helix_angle) = self.helix_angle {
+            check_range(&self.id, "helix angle", helix_angle, HELIX_ANGLE_RANGE)?;
+        }
+
+        match self.shape {
+            ToolShape::Cylindrical => {}
+            ToolShape::Conical => {
+                let tip_angle = self.tip_angle.ok_or_else(|| format!("Conical tool '{}' needs a tip angle", self.id))?;
+                check_range(&self.id, "tip angle", tip_angle, TIP_ANGLE_RANGE)?;
+                if let Some(tip_diameter) = self.tip_diameter {
+                    check_range(&self.id, "tip diameter", tip_diameter, (0.0, self.diameter))?;
+                }
+            }
+            ToolShape::Ballnose => {
+                let ball_radius = self.ball_radius.ok_or_else(|| format!("Ballnose tool '{}' needs a ball radius", self.id))?;
+                check_range(&self.id, "ball radius", ball_radius, (0.05, self.diameter / 2.0))?;
+            }
+            ToolShape::Radial => {
+                let corner_radius = self.corner_radius.ok_or_else(|| format!("Radial tool '{}' needs a corner radius", self.id))?;
+                check_range(&self.id, "corner radius", corner_radius, (0.05, self.diameter / 2.0))?;
+            }
+            ToolShape::Special => {
+                if self.special_type.as_deref().is_none_or(|t| t.trim().is_empty()) {
+                    return Err(format!("Special tool '{}' needs a special type", self.id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Depth at which a V-bit's cone reaches its full diameter, cutting deeper widens the groove
+    /// with the straight flutes. `None` for tools that are not conical.
+    pub fn v_bit_cutting_depth(&self) -> Option<f64> {
+        if self.shape != ToolShape::Conical {
+            return None;
+        }
+        let half_angle = self.tip_angle?.to_radians() / 2.0;
+        Some((self.diameter - self.tip_diameter.unwrap_or_default()) / 2.0 / half_angle.tan())
+    }
+}
+
+fn check_range(id: &str, name: &str, value: f64, (min, max): (f64, f64)) -> Result<(), String> {
+    if !(min..=max).contains(&value) {
+        return Err(format!("Tool '{}': {} must be between {} and {}, got {}", id, name, min, max, value));
+    }
+    Ok(())
+}
+
+/// The tool database file, kept next to settings.ini.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolLibrary {
+    pub version: u32,
+    pub tools: Vec<CncTool>,
+}
+
+impl Default for ToolLibrary {
+    fn default() -> Self {
+        Self { version: TOOL_LIBRARY_VERSION, tools: default_tools() }
+    }
+}
+
+// Exported libraries and plain tool lists saved by the frontend can both be imported
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ToolFile {
+    Library(ToolLibrary),
+    Tools(Vec<CncTool>),
+}
+
+impl ToolLibrary {
+    /// Loads the library, the default tool set when the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::read(path)
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tool library '{}': {}", path.display(), e))?;
+        let tools = match serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid tool library '{}': {}", path.display(), e))?
+        {
+            ToolFile::Library(library) => {
+                if library.version > TOOL_LIBRARY_VERSION {
+                    return Err(format!(
+                        "Tool library '{}' has version {}, this version reads up to {}",
+                        path.display(), library.version, TOOL_LIBRARY_VERSION
+                    ));
+                }
+                library.tools
+            }
+            ToolFile::Tools(tools) => tools,
+        };
+
+        let mut library = Self { version: TOOL_LIBRARY_VERSION, tools: Vec::with_capacity(tools.len()) };
+        for tool in tools {
+            library.add(tool)?;
+        }
+        Ok(library)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize tool library: {}", e))?;
+        fs::write(path, content)
+            .map_err(|e| format!("Failed to write tool library '{}': {}", path.display(), e))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CncTool> {
+        self.tools.iter().find(|tool| tool.id == id)
+    }
+
+    pub fn add(&mut self, tool: CncTool) -> Result<(), String> {
+        tool.validate()?;
+        if self.get(&tool.id).is_some() {
+            return Err(format!("A tool with id '{}' already exists", tool.id));
+        }
+        self.tools.push(tool);
+        Ok(())
+    }
+
+    pub fn update(&mut self, tool: CncTool) -> Result<(), String> {
+        tool.validate()?;
+        let existing = self.tools.iter_mut().find(|t| t.id == tool.id)
+            .ok_or_else(|| format!("Tool '{}' does not exist", tool.id))?;
+        *existing = tool;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let count = self.tools.len();
+        self.tools.retain(|tool| tool.id != id);
+        self.tools.len() != count
+    }
+
+    /// Adds the tools of another library file; tools with an existing id replace it.
+    /// With `replace` the current tools are dropped first. Returns the number of imported tools.
+    pub fn import(&mut self, path: &Path, replace: bool) -> Result<usize, String> {
+        let imported = Self::read(path)?.tools;
+        let count = imported.len();
+        if replace {
+            self.tools.clear();
+        }
+        for tool in imported {
+            match self.tools.iter_mut().find(|t| t.id == tool.id) {
+                Some(existing) => *existing = tool,
+                None => self.tools.push(tool),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Tool for a layer: one that lists the layer, otherwise the closest tool of the shape,
+    /// diameter or V angle the layer name asks for. `None` for layers that are not machined.
+    pub fn resolve_for_layer(&self, layer_name: &str) -> Option<&CncTool> {
+        if let Some(tool) = self.tools.iter().find(|tool| tool.layer_names.iter().any(|l| l == layer_name)) {
+            return Some(tool);
+        }
+
+        let spec = parse_layer_name(layer_name);
+        let shape = spec.tool_shape?;
+        let candidates = || self.tools.iter().filter(move |tool| tool.shape == shape);
+
+        if let (ToolShape::Conical, Some(angle)) = (shape, spec.angle) {
+            return candidates()
+                .filter_map(|tool| tool.tip_angle.map(|tip_angle| (tool, (tip_angle - angle).abs())))
+                .filter(|(_, difference)| *difference <= MAX_ANGLE_DIFFERENCE)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(tool, _)| tool);
+        }
+        if let Some(diameter) = spec.diameter {
+            return candidates()
+                .map(|tool| (tool, (tool.diameter - diameter).abs()))
+                .filter(|(_, difference)| *difference <= MAX_DIAMETER_DIFFERENCE)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(tool, _)| tool);
+        }
+
+        // No size in the name: the largest cutter clears pockets fastest, V cuts want the finest one
+        match spec.operation {
+            LayerOperation::Pocketing => candidates().max_by(|a, b| a.diameter.total_cmp(&b.diameter)),
+            LayerOperation::VCutting => candidates().min_by(|a, b| a.diameter.total_cmp(&b.diameter)),
+            _ => candidates().next(),
+        }
+    }
+}
+
+// Same set the frontend's cncToolService starts with
+fn default_tools() -> Vec<CncTool> {
+    let end_mill = |id: &str, name: &str, diameter: f64, length: f64, flutes: u32, helix_angle: f64, description: &str, material: &str, coating: &str| CncTool {
+        flutes: Some(flutes),
+        helix_angle: Some(helix_angle),
+        description: Some(description.to_string()),
+        material: Some(material.to_string()),
+        coating: Some(coating.to_string()),
+        ..CncTool::new(id, name, ToolShape::Cylindrical, diameter, length)
+    };
+    let v_bit = |id: &str, name: &str, diameter: f64, length: f64, tip_angle: f64, tip_diameter: f64, description: &str| CncTool {
+        tip_angle: Some(tip_angle),
+        tip_diameter: Some(tip_diameter),
+        flutes: Some(2),
+        description: Some(description.to_string()),
+        material: Some("Carbide".to_string()),
+        ..CncTool::new(id, name, ToolShape::Conical, diameter, length)
+    };
+    let ball_end_mill = |id: &str, name: &str, diameter: f64, length: f64, description: &str| CncTool {
+        ball_radius: Some(diameter / 2.0),
+        flutes: Some(2),
+        helix_angle: Some(30.0),
+        description: Some(description.to_string()),
+        material: Some("Carbide".to_string()),
+        ..CncTool::new(id, name, ToolShape::Ballnose, diameter, length)
+    };
+
+    let mut dovetail_parameters = serde_json::Map::new();
+    dovetail_parameters.insert("angle".to_string(), 14.into());
+    dovetail_parameters.insert("neckDiameter".to_string(), 6.into());
+    dovetail_parameters.insert("cuttingLength".to_string(), 15.into());
+
+    vec![
+        end_mill("cyl-3mm", "3mm End Mill", 3.0, 40.0, 2, 30.0, "Small end mill for fine detail work", "Carbide", "TiN"),
+        end_mill("cyl-6mm", "6mm End Mill", 6.0, 50.0, 2, 30.0, "General purpose 2-flute end mill for roughing and finishing", "HSS", "TiN"),
+        end_mill("cyl-10mm", "10mm End Mill", 10.0, 60.0, 3, 35.0, "Heavy-duty 3-flute end mill for material removal", "Carbide", "TiAlN"),
+        end_mill("cyl-20mm", "20mm End Mill", 20.0, 80.0, 4, 35.0, "Large end mill for heavy material removal", "Carbide", "TiAlN"),
+        v_bit("con-45deg", "45° V-Bit", 12.0, 30.0, 45.0, 0.1, "45° V-bit for decorative grooves"),
+        v_bit("con-60deg", "60° V-Bit", 15.0, 35.0, 60.0, 0.1, "Narrow angle V-bit for fine detail work"),
+        v_bit("con-90deg", "90° V-Bit", 20.0, 40.0, 90.0, 0.2, "Sharp V-bit for precise grooves and chamfers"),
+        ball_end_mill("ball-3mm", "3mm Ball End Mill", 3.0, 40.0, "Small ball end mill for fine detail work"),
+        CncTool {
+            coating: Some("TiAlN".to_string()),
+            ..ball_end_mill("ball-6mm", "6mm Ball End Mill", 6.0, 50.0, "Ball end mill for 3D contouring and smooth finishes")
+        },
+        CncTool {
+            corner_radius: Some(2.0),
+            flutes: Some(2),
+            helix_angle: Some(30.0),
+            description: Some("Corner rounding tool for edge finishing".to_string()),
+            material: Some("Carbide".to_string()),
+            ..CncTool::new("rad-2mm", "2mm Corner Radius", ToolShape::Radial, 8.0, 45.0)
+        },
+        CncTool {
+            special_type: Some("dovetail".to_string()),
+            custom_parameters: Some(dovetail_parameters),
+            description: Some("Dovetail cutter for joinery work".to_string()),
+            material: Some("Carbide".to_string()),
+            ..CncTool::new("special-dovetail", "Dovetail Cutter", ToolShape::Special, 12.0, 40.0)
+        },
+    ]
+}
@@ -1199,8 +1199,10 @@ export class CNCToolService {
     const centerY = command.y1
     const radius = command.radius
 
-    // For arcs, create a swept tool mesh along the arc path
-    const arcPath = this.generateArcPath(centerX, centerY, radius, command.startAngle || 0, command.endAngle || Math.PI)
+    // For arcs, create a swept tool mesh along the arc path; draw commands carry crcl's angles in degrees
+    const startAngle = (command.start_angle * Math.PI) / 180
+    const endAngle = (command.end_angle * Math.PI) / 180
+    const arcPath = this.generateArcPath(centerX, centerY, radius, startAngle, endAngle)
 
     if (operation === 'profiling' || operation === 'finishing') {
       return this.createToolSweepMesh(tool, arcPath, operation)
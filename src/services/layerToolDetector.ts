@@ -32,6 +32,8 @@ export class LayerToolDetector {
    * H = Contour (DIS/dis = outer, IC/ic = inner)
    * V = Conical tool operations
    *
+   * The backend's layer_tool.rs is a port of this parser, keep both in sync.
+   *
    * Excludes non-machinable layers:
    * - LUA layers (script-related)
    * - LMM layers (measurement/markup)
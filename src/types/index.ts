@@ -189,6 +189,7 @@ export interface BaseTool {
   coating?: string
   manufacturer?: string
   partNumber?: string
+  layerNames?: string[] // Layers always cut with this tool
 }
 
 export interface CylindricalTool extends BaseTool {
@@ -247,20 +248,63 @@ export interface DoorMachiningProfile {
   tools: CNCTool[]
 }
 
-export interface DrawCommand {
-  command_type: string
+// Pen and layer attributes every draw command carries
+export interface BaseDrawCommand {
+  color: string
+  size: number
+  layer_name: string
+  thickness: number | null // Thickness/depth information for 3D operations
+}
+
+export interface LineCommand extends BaseDrawCommand {
+  command_type: 'line'
   x1: number
   y1: number
   x2: number
   y2: number
+}
+
+export interface ArcCommand extends BaseDrawCommand {
+  command_type: 'arc'
+  x1: number
+  y1: number
   radius: number
-  color: string
-  size: number
+  start_angle: number // Degrees as passed to crcl
+  end_angle: number
+}
+
+export interface CircleCommand extends BaseDrawCommand {
+  command_type: 'circle'
+  x1: number
+  y1: number
+  radius: number
+}
+
+export interface RectangleCommand extends BaseDrawCommand {
+  command_type: 'rectangle'
+  x1: number
+  y1: number
+  x2: number
+  y2: number
+  radius: number // Corner radius
+}
+
+export interface TextCommand extends BaseDrawCommand {
+  command_type: 'text'
+  x1: number
+  y1: number
   text: string
-  layer_name: string
-  thickness?: number // Thickness/depth information for 3D operations
 }
 
+export interface PointCommand extends BaseDrawCommand {
+  command_type: 'point'
+  x1: number
+  y1: number
+}
+
+// Tagged on command_type like the backend's DrawCommand enum, each kind has only its own fields
+export type DrawCommand = LineCommand | ArcCommand | CircleCommand | RectangleCommand | TextCommand | PointCommand
+
 // Three.js tool geometry interfaces for CSG operations
 export interface ToolGeometry {
   mesh: THREE.Mesh
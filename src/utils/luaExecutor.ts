@@ -1,31 +1,352 @@
 import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type { DrawCommand, ToolShape } from '@/types'
 
-export interface DrawCommand {
-  command_type: string
-  x1: number
-  y1: number
-  x2: number
-  y2: number
-  radius: number
-  color: string
-  size: number
-  text: string
+export type { DrawCommand }
+
+export type ExecutionStatus =
+  | 'completed'
+  | 'failed'
+  | 'timed_out'
+  | 'instruction_limit_exceeded'
+  | 'cancelled'
+  | 'resource_limit_exceeded'
+
+export interface ResourceLimitError {
+  kind: 'memory' | 'call_depth'
+  message: string
+  peak_memory_bytes: number
+  memory_limit_bytes: number
+  max_call_depth: number
+}
+
+export interface SourceLocation {
+  origin: 'generated' | 'user_script' | 'debug_mode_prelude' | 'product_mode_prelude' | 'fallback_block'
+  file: string
+  line: number
+}
+
+export interface StackFrame {
+  function: string | null
+  chunk: string
+  line: number | null
+}
+
+export interface LuaErrorInfo {
+  kind: 'syntax' | 'runtime' | 'memory' | 'callback'
+  message: string
+  location: SourceLocation | null
+  frames: StackFrame[]
+}
+
+export interface ExecutionStopMarker {
+  output_line_index: number
+  draw_command_index: number
+  location: SourceLocation | null
+  frame: StackFrame | null
+}
+
+export interface ShapeNode {
+  x: number
+  y: number
+  z: number
+  bulge: number
+}
+
+export type PanelFace = 'top' | 'bottom' | 'left' | 'right' | 'rear' | 'front'
+
+export type LayerOperation =
+  | 'grooving'
+  | 'v-grooving'
+  | 'contouring'
+  | 'inner-contouring'
+  | 'outer-contouring'
+  | 'v-cutting'
+  | 'pocketing'
+  | 'profiling'
+  | 'machining'
+  | 'edge-profiling'
+  | 'non-machinable'
+  | 'prohibited'
+
+// Operation and tool parsed from a layer name by the backend
+export interface LayerToolSpec {
+  operation: LayerOperation
+  surface: PanelFace | null
+  tool_shape: ToolShape | null
+  diameter: number | null
+  angle: number | null
+  depth: number | null
+  is_inner: boolean
+  is_outer: boolean
+}
+
+// One entry of ADekoLib's `data` table
+export interface ModelShape {
   layer_name: string
-  thickness?: number
+  thickness: number
+  nodes: ShapeNode[]
+  rotation: [number, number, number][]
+  translation: [number, number, number]
+  face: PanelFace | null
+  tool_spec: LayerToolSpec
+}
+
+export interface EdgeBand {
+  layer_name: string
+  thickness: number
+}
+
+export interface ModelPart {
+  name: string
+  description: string
+  material: string
+  width: number
+  height: number
+  is_door: boolean
+  ignore_material_grain: boolean
+  edge_bands: EdgeBand[]
+  shapes: ModelShape[]
+}
+
+export interface PartModel {
+  shapes: ModelShape[]
+  parts: ModelPart[]
 }
 
 export interface LuaExecutionResult {
   success: boolean
+  status: ExecutionStatus
   output: string
   error: string
   execution_time_ms: number
   draw_commands: DrawCommand[]
+  resource_limit?: ResourceLimitError | null
+  error_details?: LuaErrorInfo | null
+  stopped_at?: ExecutionStopMarker | null
+  part_model?: PartModel | null
+  streamed: boolean
+  output_line_count: number
+  draw_command_count: number
+}
+
+/**
+ * Incremental output of a streamed run, emitted as the `lua-execution-batch` event
+ */
+export interface LuaExecutionBatch {
+  run_id: string | null
+  sequence: number
+  output: string[]
+  draw_commands: DrawCommand[]
+}
+
+/**
+ * Panel the model is built for; omitted fields use the backend defaults (500x700x18 door)
+ */
+export interface ModelParameters {
+  x?: number
+  y?: number
+  z?: number
+  material_thickness?: number
+  body_material_thickness?: number
+  door_material_thickness?: number
+  body_material?: string
+  door_material?: string
+  edge_bands?: [EdgeBand, EdgeBand, EdgeBand, EdgeBand]
+  model_parameters?: string
+  does_size_include_edge_thickness?: boolean
+  mdir?: 0 | 90 | 180 | 270 | null
 }
 
 export interface LuaExecutorOptions {
   scriptContent: string
   luaLibraryPath: string
   debugMode: boolean
+  productMode?: boolean // Runs productMain() through ADekoProductMode.lua, takes precedence over debugMode
+  scriptName?: string
+  runId?: string
+  timeoutMs?: number
+  instructionLimit?: number
+  memoryLimitMb?: number
+  maxCallDepth?: number
+  streamEvents?: boolean
+  modelParameters?: ModelParameters
+}
+
+/**
+ * Parameter grid for sweep_model; every non-empty axis is combined with all others
+ */
+export interface ModelSweep {
+  base?: ModelParameters
+  x?: number[]
+  y?: number[]
+  material_thickness?: number[]
+  edge_thickness?: number[]
+  model_parameters?: string[]
+  cases?: ModelParameters[]
+}
+
+export type SweepIssue = 'error' | 'no_shapes' | 'outside_panel'
+
+export interface OutsidePanelShape {
+  part_name: string | null
+  shape_index: number
+  layer_name: string
+  face: PanelFace
+  min: [number, number]
+  max: [number, number]
+  face_size: [number, number]
+}
+
+export interface SweepCaseResult {
+  index: number
+  parameters: ModelParameters
+  status: ExecutionStatus
+  error: string | null
+  shape_count: number
+  outside_panel: OutsidePanelShape[]
+  issues: SweepIssue[]
+  execution_time_ms: number
+}
+
+export interface SweepReport {
+  total_cases: number
+  completed_cases: number
+  failed_cases: number
+  cases: SweepCaseResult[]
+}
+
+export interface ModelSweepOptions {
+  scriptContent: string
+  luaLibraryPath: string
+  debugMode: boolean
+  productMode?: boolean
+  scriptName?: string
+  sweep: ModelSweep
+  runId?: string
+  timeoutMs?: number
+  instructionLimit?: number
+}
+
+export type ArcMode = 'relative' | 'radial'
+
+/**
+ * Post-processor settings for export_gcode, named after the PostProcessorCommonsLib.lua globals
+ */
+export interface PostProcessorConfig {
+  program_name?: string
+  header?: string[]
+  footer?: string[]
+  comment_start?: string
+  comment_end?: string
+  line_number_step?: number
+  extra_zero_in_g_codes?: boolean
+  extra_zero_in_m_codes?: boolean
+  toggle_operation_labels?: boolean
+  tool_no_before_m_code?: boolean
+  spindle_rpm_before_m_code?: boolean
+  tool_select_specifier?: string
+  arc_mode?: ArcMode
+  face?: PanelFace
+  safe_z?: number
+  spindle_max?: number
+}
+
+export interface ToolMapping {
+  layer_name: string
+  tool_number: number
+  spindle_speed: number
+  clockwise: boolean
+  feed_rate: number
+  plunge_rate: number
+  step_down: number
+  compensation?: 'on' | 'inside' | 'outside' // Side of closed shapes the tool cuts on, 'on' by default
+  tool_diameter?: number | null // Defaults to the diameter in the layer name
+}
+
+export interface SimulationOptions {
+  cell_size?: number
+  face?: PanelFace
+  material_thickness?: number
+  panel_width?: number
+  panel_height?: number
+}
+
+// Heights are relative to the panel surface, row-major from origin along +X then +Y
+export interface Heightmap {
+  columns: number
+  rows: number
+  cell_size: number
+  origin: [number, number]
+  heights: number[]
+}
+
+export interface LayerRemoval {
+  layer_name: string
+  tool_id: string | null
+  tool_shape: ToolShape
+  tool_diameter: number
+  shape_count: number
+  removed_volume: number
+  max_depth: number
+}
+
+export interface SimulationResult {
+  heightmap: Heightmap
+  layers: LayerRemoval[]
+  removed_volume: number
+}
+
+export type CheckRule =
+  | 'self_intersection'
+  | 'open_contour'
+  | 'outside_panel'
+  | 'through_depth'
+  | 'duplicate_shape'
+  | 'narrow_feature'
+  | 'sharp_inner_corner'
+  | 'v_bit_too_deep'
+
+export interface ModelDiagnostic {
+  rule: CheckRule
+  severity: 'error' | 'warning'
+  part_name: string | null
+  shape_index: number
+  layer_name: string
+  message: string
+  location: [number, number] | null
+}
+
+export interface ModelCheckReport {
+  shape_count: number
+  error_count: number
+  warning_count: number
+  diagnostics: ModelDiagnostic[]
+}
+
+export interface MeshSummary {
+  vertex_count: number
+  triangle_count: number
+}
+
+export interface GeometryPoint {
+  x: number
+  y: number
+}
+
+export interface GeometryBounds {
+  min: GeometryPoint
+  max: GeometryPoint
+}
+
+export interface ShapeOutline {
+  layer_name: string
+  face: PanelFace | null
+  closed: boolean
+  length: number
+  area: number | null // Signed, positive for counter-clockwise shapes
+  bounds: GeometryBounds | null
+  points: GeometryPoint[]
 }
 
 export interface LuaSyntaxError {
@@ -54,19 +375,167 @@ export async function executeLuaScript(options: LuaExecutorOptions): Promise<Lua
     return await invoke<LuaExecutionResult>('execute_lua_script', {
       scriptContent: options.scriptContent,
       luaLibraryPath: options.luaLibraryPath,
-      debugMode: options.debugMode
+      debugMode: options.debugMode,
+      productMode: options.productMode,
+      scriptName: options.scriptName,
+      runId: options.runId,
+      timeoutMs: options.timeoutMs,
+      instructionLimit: options.instructionLimit,
+      memoryLimitMb: options.memoryLimitMb,
+      maxCallDepth: options.maxCallDepth,
+      streamEvents: options.streamEvents,
+      modelParameters: options.modelParameters
     })
   } catch (error) {
     return {
       success: false,
+      status: 'failed',
       output: '',
       error: `Failed to execute Lua script: ${error}`,
       execution_time_ms: 0,
-      draw_commands: []
+      draw_commands: [],
+      streamed: false,
+      output_line_count: 0,
+      draw_command_count: 0
     }
   }
 }
 
+/**
+ * Run a model over a grid of parameters and report the combinations that fail
+ */
+export async function sweepModel(options: ModelSweepOptions): Promise<SweepReport> {
+  return await invoke<SweepReport>('sweep_model', {
+    scriptContent: options.scriptContent,
+    luaLibraryPath: options.luaLibraryPath,
+    debugMode: options.debugMode,
+    productMode: options.productMode,
+    scriptName: options.scriptName,
+    sweep: options.sweep,
+    runId: options.runId,
+    timeoutMs: options.timeoutMs,
+    instructionLimit: options.instructionLimit
+  })
+}
+
+/**
+ * Write the shapes of a run, or of one product part, on one face (top by default) to a DXF file
+ */
+export async function exportDxf(
+  path: string,
+  partModel: PartModel,
+  face?: PanelFace,
+  partIndex?: number
+): Promise<void> {
+  await invoke('export_dxf', { path, partModel, partIndex, face })
+}
+
+/**
+ * Write the draw commands to an SVG file with one layer group per layer name
+ */
+export async function exportSvg(path: string, drawCommands: DrawCommand[]): Promise<void> {
+  await invoke('export_svg', { path, drawCommands })
+}
+
+/**
+ * Write G-code for the shapes whose layer has a tool mapping
+ */
+export async function exportGcode(
+  path: string,
+  partModel: PartModel,
+  toolMapping: ToolMapping[],
+  config?: PostProcessorConfig,
+  partIndex?: number
+): Promise<void> {
+  await invoke('export_gcode', { path, partModel, partIndex, config, toolMapping })
+}
+
+/**
+ * Simulate the material removal of a run on a heightmap, using the backend tool library
+ */
+export async function simulateMachining(
+  partModel: PartModel,
+  options?: SimulationOptions,
+  partIndex?: number
+): Promise<SimulationResult> {
+  return await invoke<SimulationResult>('simulate_machining', { partModel, partIndex, options })
+}
+
+/**
+ * Write the simulated machined panel as a closed binary STL mesh
+ */
+export async function saveStl(
+  path: string,
+  partModel: PartModel,
+  options?: SimulationOptions,
+  partIndex?: number
+): Promise<MeshSummary> {
+  return await invoke<MeshSummary>('save_stl', { path, partModel, partIndex, options })
+}
+
+/**
+ * Write the simulated machined panel as glTF, binary when the path ends with .glb
+ */
+export async function exportGltf(
+  path: string,
+  partModel: PartModel,
+  options?: SimulationOptions,
+  partIndex?: number
+): Promise<MeshSummary> {
+  return await invoke<MeshSummary>('export_gltf', { path, partModel, partIndex, options })
+}
+
+/**
+ * Check the shapes of a run for geometry errors the preview does not show, and against the
+ * tools the backend tool library assigns their layers
+ */
+export async function checkModel(
+  partModel: PartModel,
+  parameters?: ModelParameters,
+  productMode?: boolean
+): Promise<ModelCheckReport> {
+  return await invoke<ModelCheckReport>('check_model', { partModel, parameters, productMode })
+}
+
+/**
+ * Flatten the bulge arcs of a run's shapes into points for the preview, within chordTolerance
+ * (default 0.01 mm) of the exact arcs
+ */
+export async function tessellateShapes(
+  partModel: PartModel,
+  chordTolerance?: number,
+  partIndex?: number
+): Promise<ShapeOutline[]> {
+  return await invoke<ShapeOutline[]>('tessellate_shapes', { partModel, partIndex, chordTolerance })
+}
+
+/**
+ * Parse a layer name like K_Freze10mm into its operation and tool spec
+ */
+export async function analyzeLayer(layerName: string): Promise<LayerToolSpec> {
+  return await invoke<LayerToolSpec>('analyze_layer', { layerName })
+}
+
+/**
+ * Subscribe to the batches of streamed runs; returns the unlisten function
+ */
+export async function onLuaExecutionBatch(
+  handler: (batch: LuaExecutionBatch) => void
+): Promise<UnlistenFn> {
+  return await listen<LuaExecutionBatch>('lua-execution-batch', event => handler(event.payload))
+}
+
+/**
+ * Cancel a running script started with the given runId
+ */
+export async function cancelLuaExecution(runId: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('cancel_lua_execution', { runId })
+  } catch {
+    return false
+  }
+}
+
 /**
  * Check if native Lua is available (always true with embedded Lua)
  */
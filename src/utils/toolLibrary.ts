@@ -0,0 +1,39 @@
+import { invoke } from '@tauri-apps/api/core'
+import type { CNCTool } from '@/types'
+
+// The tool library is stored by the backend in tools.json next to settings.ini.
+// Every change returns the full, saved tool list.
+
+export async function getCncTools(): Promise<CNCTool[]> {
+  return await invoke<CNCTool[]>('get_cnc_tools')
+}
+
+export async function addCncTool(tool: CNCTool): Promise<CNCTool[]> {
+  return await invoke<CNCTool[]>('add_cnc_tool', { tool })
+}
+
+export async function updateCncTool(tool: CNCTool): Promise<CNCTool[]> {
+  return await invoke<CNCTool[]>('update_cnc_tool', { tool })
+}
+
+export async function deleteCncTool(id: string): Promise<CNCTool[]> {
+  return await invoke<CNCTool[]>('delete_cnc_tool', { id })
+}
+
+/**
+ * Import a tool library or a plain tool list; tools with an existing id replace it
+ */
+export async function importCncTools(path: string, replace = false): Promise<CNCTool[]> {
+  return await invoke<CNCTool[]>('import_cnc_tools', { path, replace })
+}
+
+export async function exportCncTools(path: string): Promise<void> {
+  await invoke('export_cnc_tools', { path })
+}
+
+/**
+ * Find a tool by id, or the tool a layer name asks for
+ */
+export async function resolveCncTool(options: { id?: string; layerName?: string }): Promise<CNCTool | null> {
+  return await invoke<CNCTool | null>('resolve_cnc_tool', { id: options.id, layerName: options.layerName })
+}